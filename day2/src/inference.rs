@@ -0,0 +1,289 @@
+// Maximum likelihood estimates of the bag a game was played with.
+//
+// Each round the elf grabs a handful of cubes without replacement, shows them, and puts them
+// back. So the chance of a round showing n_c cubes of each colour from a bag holding K_c is the
+// multivariate hypergeometric Π C(K_c, n_c) / C(N, n), where N and n are the bag and hand totals.
+// Colours never seen only make the observed hands less likely, so the estimate ignores them and
+// searches every bag from the per-colour maxima up to `max_cubes` of each observed colour.
+
+use std::collections::HashMap;
+
+use crate::Game;
+
+/// 95% quantile of χ² with one degree of freedom, halved for use on log-likelihoods
+const PROFILE_CUTOFF: f64 = 3.841_458_820_694_124 / 2.0;
+
+/// Refuse to search more bags than this for a single game
+const MAX_SEARCH: u64 = 50_000_000;
+
+/// ln(n!) for every n up to `n`
+fn ln_factorials(n: usize) -> Vec<f64> {
+    let mut table = vec![0.0; n + 1];
+    for i in 1..=n {
+        table[i] = table[i - 1] + (i as f64).ln();
+    }
+    table
+}
+
+fn ln_choose(ln_fact: &[f64], n: i64, k: i64) -> f64 {
+    if k < 0 || k > n {
+        return f64::NEG_INFINITY;
+    }
+    ln_fact[n as usize] - ln_fact[k as usize] - ln_fact[(n - k) as usize]
+}
+
+/// Log of the probability of seeing every round of `game` when drawing from `bag`.
+/// Negative infinity if some round shows more cubes of a colour than the bag holds.
+pub fn log_likelihood(game: &Game, bag: &HashMap<String, i64>) -> f64 {
+    let total: i64 = bag.values().sum();
+    let largest_hand = game.rounds.iter().map(|r| r.total()).max().unwrap_or(0);
+    if largest_hand > total {
+        return f64::NEG_INFINITY;
+    }
+    let ln_fact = ln_factorials(total as usize);
+    game.rounds
+        .iter()
+        .map(|round| {
+            let mut colours = round
+                .hands
+                .iter()
+                .map(|hand| hand.colour.as_str())
+                .collect::<Vec<&str>>();
+            colours.sort();
+            colours.dedup();
+            colours
+                .iter()
+                .map(|c| ln_choose(&ln_fact, *bag.get(*c).unwrap_or(&0), round.count(c)))
+                .sum::<f64>()
+                - ln_choose(&ln_fact, total, round.total())
+        })
+        .sum()
+}
+
+pub struct Inference {
+    pub colours: Vec<String>,
+    /// Most likely count of each colour (the smallest such bag if there are ties)
+    pub bag: Vec<i64>,
+    pub log_likelihood: f64,
+    /// 95% profile likelihood interval per colour. `true` if it reaches the search limit.
+    pub intervals: Vec<(i64, i64, bool)>,
+}
+
+pub fn infer_bag(game: &Game, max_cubes: i64) -> Result<Inference, String> {
+    let mut minimum = game
+        .minimum_bag()
+        .into_iter()
+        .collect::<Vec<(String, i64)>>();
+    minimum.sort();
+    let (colours, lower): (Vec<String>, Vec<i64>) = minimum.into_iter().unzip();
+
+    if let Some(i) = lower.iter().position(|v| *v > max_cubes) {
+        return Err(format!(
+            "Game {} needs {} {}, more than the limit of {}",
+            game.number, lower[i], colours[i], max_cubes
+        ));
+    }
+    let size = lower
+        .iter()
+        .map(|v| (max_cubes - v + 1) as u64)
+        .try_fold(1_u64, |acc, n| acc.checked_mul(n))
+        .filter(|size| *size <= MAX_SEARCH)
+        .ok_or_else(|| format!("Game {} has too many bags to search", game.number))?;
+
+    // Per-round colour counts, in the same order as `colours`
+    let rounds = game
+        .rounds
+        .iter()
+        .map(|round| {
+            (
+                colours.iter().map(|c| round.count(c)).collect::<Vec<i64>>(),
+                round.total(),
+            )
+        })
+        .collect::<Vec<(Vec<i64>, i64)>>();
+    let ln_fact = ln_factorials((max_cubes as usize) * colours.len().max(1));
+
+    let mut best: Option<(f64, Vec<i64>)> = None;
+    // Best log-likelihood seen for each count of each colour (the profile likelihood)
+    let mut profile = lower
+        .iter()
+        .map(|v| vec![f64::NEG_INFINITY; (max_cubes - v + 1) as usize])
+        .collect::<Vec<Vec<f64>>>();
+
+    let mut bag = lower.clone();
+    for _ in 0..size {
+        let total: i64 = bag.iter().sum();
+        let ll: f64 = rounds
+            .iter()
+            .map(|(counts, n)| {
+                counts
+                    .iter()
+                    .zip(&bag)
+                    .map(|(n_c, k_c)| ln_choose(&ln_fact, *k_c, *n_c))
+                    .sum::<f64>()
+                    - ln_choose(&ln_fact, total, *n)
+            })
+            .sum();
+
+        for (c, k) in bag.iter().enumerate() {
+            let p = &mut profile[c][(k - lower[c]) as usize];
+            *p = p.max(ll);
+        }
+        if best.as_ref().is_none_or(|(b, _)| ll > *b) {
+            best = Some((ll, bag.clone()));
+        }
+
+        // Odometer step to the next bag
+        for c in 0..bag.len() {
+            if bag[c] < max_cubes {
+                bag[c] += 1;
+                break;
+            }
+            bag[c] = lower[c];
+        }
+    }
+
+    let (log_likelihood, bag) = best.unwrap();
+    let intervals = profile
+        .iter()
+        .zip(&lower)
+        .map(|(p, lo)| {
+            let inside = p
+                .iter()
+                .enumerate()
+                .filter(|(_, ll)| **ll >= log_likelihood - PROFILE_CUTOFF)
+                .map(|(i, _)| lo + i as i64);
+            let (min, max) = (inside.clone().min().unwrap(), inside.max().unwrap());
+            (min, max, max == max_cubes)
+        })
+        .collect();
+
+    Ok(Inference {
+        colours,
+        bag,
+        log_likelihood,
+        intervals,
+    })
+}
+
+/// ln Γ(k / 2)
+fn ln_gamma_half(k: u32) -> f64 {
+    if k.is_multiple_of(2) {
+        ln_factorials(k as usize / 2 - 1).pop().unwrap()
+    } else {
+        (0..k / 2).fold(std::f64::consts::PI.sqrt().ln(), |acc, j| {
+            acc + (j as f64 + 0.5).ln()
+        })
+    }
+}
+
+/// Upper tail of the χ² distribution with `df` degrees of freedom, via the regularised
+/// incomplete gamma function (series below s + 1, continued fraction above)
+fn chi_squared_tail(x: f64, df: u32) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let (s, x) = (df as f64 / 2.0, x / 2.0);
+    let scale = (-x + s * x.ln() - ln_gamma_half(df)).exp();
+    if x < s + 1.0 {
+        let (mut term, mut sum) = (1.0 / s, 1.0 / s);
+        for n in 1..1000 {
+            term *= x / (s + n as f64);
+            sum += term;
+            if term < sum * 1e-15 {
+                break;
+            }
+        }
+        (1.0 - sum * scale).max(0.0)
+    } else {
+        // Lentz's method
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - s;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - s);
+            b += 2.0;
+            d = an * d + b;
+            d = if d.abs() < tiny { tiny } else { d };
+            c = b + an / c;
+            c = if c.abs() < tiny { tiny } else { c };
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        scale * h
+    }
+}
+
+pub struct Plausibility {
+    /// ln of (likelihood of the proposed bag / likelihood of the most likely bag)
+    pub log_ratio: f64,
+    /// Likelihood ratio test against the most likely bag, with a degree of freedom per colour
+    pub p_value: f64,
+}
+
+/// How improbable is it that `game` was played with `bag`, compared to the most likely bag
+pub fn plausibility(
+    game: &Game,
+    bag: &HashMap<String, i64>,
+    inference: &Inference,
+) -> Plausibility {
+    let ll = log_likelihood(game, bag);
+    // A bag beyond the search limit may do (very slightly) better than the estimate
+    let log_ratio = (ll - inference.log_likelihood).min(0.0);
+    Plausibility {
+        log_ratio,
+        p_value: match ll.is_finite() {
+            true => chi_squared_tail(-2.0 * log_ratio, inference.colours.len().max(1) as u32),
+            false => 0.0,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::inference::{chi_squared_tail, infer_bag, log_likelihood, plausibility};
+    use crate::parse_game;
+
+    #[test]
+    fn test_log_likelihood() {
+        let game = parse_game("Game 1: 1 red, 1 blue; 2 red");
+        let bag = HashMap::from([("red".to_string(), 2), ("blue".to_string(), 1)]);
+        // 2/3 chance of one of each colour, then 1/3 of both reds
+        assert!((log_likelihood(&game, &bag) - (2.0_f64 / 9.0).ln()).abs() < 1e-12);
+
+        let too_small = HashMap::from([("red".to_string(), 2)]);
+        assert_eq!(f64::NEG_INFINITY, log_likelihood(&game, &too_small));
+    }
+
+    #[test]
+    fn test_infer_bag() {
+        // Always drawing exactly three reds is certain with a bag of three reds
+        let game = parse_game("Game 2: 3 red; 3 red; 3 red");
+        let inference = infer_bag(&game, 20).unwrap();
+        assert_eq!(vec!["red".to_string()], inference.colours);
+        assert_eq!(vec![3], inference.bag);
+        assert_eq!(0.0, inference.log_likelihood);
+        // ...but so is any other all-red bag, so the interval runs up to the search limit
+        assert_eq!((3, 20, true), inference.intervals[0]);
+
+        let with_blue = HashMap::from([("red".to_string(), 3), ("blue".to_string(), 3)]);
+        let p = plausibility(&game, &with_blue, &inference);
+        assert!((p.log_ratio - 3.0 * (1.0_f64 / 20.0).ln()).abs() < 1e-12);
+        assert!(p.p_value < 0.001);
+    }
+
+    #[test]
+    fn test_chi_squared_tail() {
+        assert!((chi_squared_tail(3.841_458_820_694_124, 1) - 0.05).abs() < 1e-9);
+        assert!((chi_squared_tail(7.814_727_903_251_178, 3) - 0.05).abs() < 1e-9);
+        assert!((chi_squared_tail(1.0, 2) - (-0.5_f64).exp()).abs() < 1e-12);
+    }
+}
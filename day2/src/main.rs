@@ -1,60 +1,328 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, BufRead},
+};
+
+mod inference;
+mod query;
+
+/// `<count> <colour>` cubes pulled out of the bag
+struct Hand {
+    colour: String,
+    count: i64,
+}
+
+/// Every hand revealed together in one round of a game
+struct Round {
+    hands: Vec<Hand>,
+}
+
+impl Round {
+    fn count(&self, colour: &str) -> i64 {
+        self.hands
+            .iter()
+            .filter(|hand| hand.colour == colour)
+            .map(|hand| hand.count)
+            .sum()
+    }
+
+    fn total(&self) -> i64 {
+        self.hands.iter().map(|hand| hand.count).sum()
+    }
+}
+
 struct Game {
     number: i64,
-    red: i64,
-    blue: i64,
-    green: i64,
+    rounds: Vec<Round>,
+}
+
+impl Game {
+    /// Largest number of cubes of a colour revealed in any one round
+    fn max(&self, colour: &str) -> i64 {
+        self.rounds
+            .iter()
+            .map(|round| round.count(colour))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The smallest bag this game could have been played with, i.e. the per-colour maxima
+    fn minimum_bag(&self) -> HashMap<String, i64> {
+        let mut cubes = HashMap::<String, i64>::new();
+        for round in &self.rounds {
+            for hand in &round.hands {
+                let max = cubes.entry(hand.colour.clone()).or_insert(0);
+                *max = (*max).max(round.count(&hand.colour));
+            }
+        }
+        cubes
+    }
+
+    /// Product of the fewest cubes of each colour the game needed, so a colour it never showed
+    /// makes this 0
+    fn power(&self, colours: &[String]) -> i64 {
+        colours.iter().map(|colour| self.max(colour)).product()
+    }
+}
+
+/// The puzzle's red, green and blue, then any other colours the games show
+fn colours(games: &[Game]) -> Vec<String> {
+    let mut colours = ["red", "green", "blue"].map(String::from).to_vec();
+    for game in games {
+        for round in &game.rounds {
+            for hand in &round.hands {
+                if !colours.contains(&hand.colour) {
+                    colours.push(hand.colour.clone());
+                }
+            }
+        }
+    }
+    colours
+}
+
+/// A bag of cubes, i.e. `12 red, 13 green, 14 blue`. Colours not listed have no cubes in the bag.
+struct Bag {
+    name: String,
+    limits: HashMap<String, i64>,
+}
+
+impl Bag {
+    fn from_str(s: &str) -> Bag {
+        let mut limits = HashMap::<String, i64>::new();
+        for (colour, v) in parse_hand(s) {
+            *limits.entry(colour).or_insert(0) += v;
+        }
+        Bag {
+            name: s.trim().to_string(),
+            limits,
+        }
+    }
+
+    fn allows(&self, game: &Game) -> bool {
+        game.minimum_bag()
+            .iter()
+            .all(|(colour, v)| v <= self.limits.get(colour).unwrap_or(&0))
+    }
+}
+
+/// Parse a comma separated list of `<count> <colour>` pairs
+fn parse_hand(hand: &str) -> impl Iterator<Item = (String, i64)> + '_ {
+    hand.split(',')
+        .map(str::trim)
+        .filter(|cubes| !cubes.is_empty())
+        .map(|cubes| {
+            let (v, colour) = cubes
+                .split_once(' ')
+                .unwrap_or_else(|| panic!("Expected '<count> <colour>', found '{cubes}'"));
+            (colour.trim().to_string(), v.parse::<i64>().unwrap())
+        })
 }
 
 fn parse_game(line: &str) -> Game {
     let (game_name, game_rounds) = line.split_once(": ").unwrap();
-    let (_, game_number_str) = game_name.split_once(" ").unwrap();
+    let (_, game_number_str) = game_name.split_once(' ').unwrap();
 
-    let mut game = Game {
+    Game {
         number: game_number_str.parse::<i64>().unwrap(),
-        red: 0,
-        blue: 0,
-        green: 0,
-    };
+        rounds: game_rounds
+            .split("; ")
+            .map(|round| Round {
+                hands: parse_hand(round)
+                    .map(|(colour, count)| Hand { colour, count })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
 
-    for round in game_rounds.split("; ") {
-        for hand in round.split(", ") {
-            let mut parts = hand.split(" ");
-            let _ = match (
-                parts.next().unwrap().parse::<i64>().unwrap(),
-                parts.next().unwrap(),
-            ) {
-                (v, "red") => game.red = game.red.max(v),
-                (v, "green") => game.green = game.green.max(v),
-                (v, "blue") => game.blue = game.blue.max(v),
-                (_, _) => panic!("Oh no!"),
-            };
+struct Options {
+    bags: Vec<Bag>,
+    queries: Vec<String>,
+    interactive: bool,
+    infer: bool,
+    max_cubes: i64,
+}
+
+/// Bags come from `--bag "<cubes>"` arguments and/or `--config <file>` (one bag per line, `#` comments).
+/// Queries come from `--query "<query>"`, or one per line on stdin with `--interactive`.
+/// `--infer` estimates each game's bag (searching up to `--max-cubes` of a colour) and rates the bags.
+fn parse_args(args: &[String]) -> Options {
+    let mut options = Options {
+        bags: Vec::new(),
+        queries: Vec::new(),
+        interactive: false,
+        infer: false,
+        max_cubes: 40,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bag" => options
+                .bags
+                .push(Bag::from_str(args.next().expect("--bag needs a value"))),
+            "--config" => {
+                let path = args.next().expect("--config needs a path");
+                let config = fs::read_to_string(path)
+                    .unwrap_or_else(|e| panic!("Couldn't read config {path}: {e}"));
+                options.bags.extend(
+                    config
+                        .lines()
+                        .map(|line| line.split('#').next().unwrap().trim())
+                        .filter(|line| !line.is_empty())
+                        .map(Bag::from_str),
+                );
+            }
+            "--query" => options
+                .queries
+                .push(args.next().expect("--query needs a value").to_string()),
+            "--interactive" => options.interactive = true,
+            "--infer" => options.infer = true,
+            "--max-cubes" => {
+                options.max_cubes = args
+                    .next()
+                    .expect("--max-cubes needs a value")
+                    .parse::<i64>()
+                    .expect("--max-cubes should be a number")
+            }
+            _ => panic!("Unknown argument {arg}"),
         }
     }
-    game
+
+    if options.bags.is_empty() {
+        // The bag from the puzzle
+        options
+            .bags
+            .push(Bag::from_str("12 red, 13 green, 14 blue"));
+    }
+    options
 }
 
 fn main() {
-    // Part 1
-    let possible_game_sum_part_1: i64 = include_str!("day2.txt")
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let options = parse_args(&args);
+    let bags = &options.bags;
+    let games = include_str!("day2.txt")
         .lines()
         .map(parse_game)
-        .map(|g| {
-            if g.red <= 12 && g.green <= 13 && g.blue <= 14 {
-                g.number
-            } else {
-                0
+        .collect::<Vec<Game>>();
+
+    // Part 1 (for every bag in one pass over the games)
+    let mut possible_game_sums = vec![0_i64; bags.len()];
+    for game in &games {
+        for (i, bag) in bags.iter().enumerate() {
+            if bag.allows(game) {
+                possible_game_sums[i] += game.number;
             }
-        })
-        .sum();
+        }
+    }
 
-    println!("{:?}", possible_game_sum_part_1);
+    for (bag, sum) in bags.iter().zip(possible_game_sums) {
+        println!("{}: {:?}", bag.name, sum);
+    }
 
     // Part 2
-    let possible_game_sum_part_2: i64 = include_str!("day2.txt")
-        .lines()
-        .map(parse_game)
-        .map(|g| g.red * g.green * g.blue)
-        .sum();
+    let colours = colours(&games);
+    let possible_game_sum_part_2: i64 = games.iter().map(|game| game.power(&colours)).sum();
 
     println!("{:?}", possible_game_sum_part_2);
+
+    // Which bags were the games most likely played with?
+    if options.infer {
+        for game in &games {
+            let inference = match inference::infer_bag(game, options.max_cubes) {
+                Ok(inference) => inference,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    continue;
+                }
+            };
+            let describe = |f: &dyn Fn(usize) -> String| {
+                (0..inference.colours.len())
+                    .map(|i| format!("{} {}", f(i), inference.colours[i]))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            };
+            println!(
+                "Game {}: most likely {} (log-likelihood {:.3}), 95% intervals {}",
+                game.number,
+                describe(&|i| inference.bag[i].to_string()),
+                inference.log_likelihood,
+                describe(&|i| {
+                    let (lo, hi, open) = inference.intervals[i];
+                    format!("{}..{}{}", lo, hi, if open { "+" } else { "" })
+                }),
+            );
+            for bag in bags {
+                let p = inference::plausibility(game, &bag.limits, &inference);
+                match p.log_ratio.is_finite() {
+                    true => println!(
+                        "  {}: {:.3e} times as likely, p = {:.3e}",
+                        bag.name,
+                        p.log_ratio.exp(),
+                        p.p_value
+                    ),
+                    false => println!("  {}: impossible", bag.name),
+                }
+            }
+        }
+    }
+
+    // Exploring the games
+    for q in &options.queries {
+        match query::run(q, &games) {
+            Ok(result) => println!("{}", result),
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+
+    if options.interactive {
+        for line in io::stdin().lock().lines() {
+            let line = line.unwrap();
+            if line.trim().is_empty() {
+                continue;
+            }
+            match query::run(&line, &games) {
+                Ok(result) => println!("{}", result),
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_game;
+    use crate::{colours, Bag};
+
+    #[test]
+    fn test_bag_allows_any_colour() {
+        let game = parse_game("Game 7: 3 blue, 4 purple; 1 red, 2 green, 6 blue; 2 purple");
+        assert_eq!(3, game.rounds.len());
+        assert_eq!(4, game.max("purple"));
+        assert_eq!(6, game.max("blue"));
+
+        assert!(!Bag::from_str("12 red, 13 green, 14 blue").allows(&game));
+        assert!(Bag::from_str("1 red, 2 green, 6 blue, 4 purple").allows(&game));
+        assert!(!Bag::from_str("1 red, 2 green, 6 blue, 3 purple").allows(&game));
+    }
+
+    #[test]
+    fn test_power_with_missing_colour() {
+        let games = [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 4 red, 2 green; 1 red",
+        ]
+        .map(parse_game);
+        let puzzle = colours(&games);
+        assert_eq!(vec!["red", "green", "blue"], puzzle);
+        assert_eq!(48, games[0].power(&puzzle));
+        // Never showing blue means no blue cubes were needed
+        assert_eq!(0, games[1].power(&puzzle));
+
+        // Other colours count once any game shows them
+        let purple = parse_game("Game 3: 1 red, 2 green, 3 blue, 4 purple");
+        let with_purple = colours(&[purple]);
+        assert_eq!(0, games[0].power(&with_purple));
+    }
 }
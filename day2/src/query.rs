@@ -0,0 +1,462 @@
+// A small filter/aggregate language over the parsed games, e.g.
+//
+//   red > 5 && rounds >= 3        games matching a filter
+//   sum(power) where blue < 4     aggregate over the matching games
+//   max(green) by game            aggregate over the rounds of each game
+//
+// Inside an expression a colour is the game's maximum for that colour (or the count in the
+// round when grouping `by game`). `game`, `rounds` and `power` describe the whole game, while
+// `round` and `cubes` are the round number and total cubes drawn in the round. Booleans are 0/1.
+// Any other name, or `round` and `cubes` outside `by game`, is an error rather than a colour
+// that was never drawn.
+
+use std::fmt;
+
+use crate::{colours, Game, Round};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+// Longest operators first, so `<=` isn't read as `<` then `=`
+const OPERATORS: [&str; 14] = [
+    "&&", "||", "<=", ">=", "==", "!=", "<", ">", "!", "+", "-", "*", "/", "%",
+];
+
+fn tokenise(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::<Token>::new();
+    let mut rest = input.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let value = rest[..end]
+                .parse::<i64>()
+                .map_err(|e| format!("Bad number {}: {e}", &rest[..end]))?;
+            tokens.push(Token::Num(value));
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c == '(' || c == ')' {
+            tokens.push(if c == '(' {
+                Token::LParen
+            } else {
+                Token::RParen
+            });
+            rest = &rest[1..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(format!("Unexpected '{c}'"));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Expr {
+    Num(i64),
+    Field(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(Box<Expr>, &'static str, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, field: &dyn Fn(&str) -> Result<i64, String>) -> Result<i64, String> {
+        Ok(match self {
+            Expr::Num(v) => *v,
+            Expr::Field(name) => field(name)?,
+            Expr::Unary("!", e) => (e.eval(field)? == 0) as i64,
+            Expr::Unary(_, e) => e.eval(field)?.checked_neg().ok_or("Overflow in negation")?,
+            Expr::Binary(l, op, r) => {
+                let (l, r) = (l.eval(field)?, r.eval(field)?);
+                let overflow = || format!("Overflow in {l} {op} {r}");
+                match *op {
+                    "&&" => (l != 0 && r != 0) as i64,
+                    "||" => (l != 0 || r != 0) as i64,
+                    "<" => (l < r) as i64,
+                    "<=" => (l <= r) as i64,
+                    ">" => (l > r) as i64,
+                    ">=" => (l >= r) as i64,
+                    "==" => (l == r) as i64,
+                    "!=" => (l != r) as i64,
+                    "+" => l.checked_add(r).ok_or_else(overflow)?,
+                    "-" => l.checked_sub(r).ok_or_else(overflow)?,
+                    "*" => l.checked_mul(r).ok_or_else(overflow)?,
+                    "/" | "%" if r == 0 => return Err(format!("Division by zero in {l} {op} 0")),
+                    "/" => l.checked_div(r).ok_or_else(overflow)?,
+                    _ => l.checked_rem(r).ok_or_else(overflow)?,
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Aggregate {
+    Sum,
+    Max,
+    Min,
+    Count,
+}
+
+#[derive(Debug)]
+enum Query {
+    Filter(Expr),
+    Aggregate {
+        aggregate: Aggregate,
+        expr: Option<Expr>,
+        filter: Option<Expr>,
+        by_game: bool,
+    },
+}
+
+/// Recursive descent parser, loosest binding first: || && ! comparisons +- */%
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.pos += 1;
+        self.tokens.get(self.pos - 1).cloned()
+    }
+
+    fn eat_op(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek() == Some(&Token::Ident(keyword.to_string())) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            token => Err(format!("Expected {:?}, found {:?}", expected, token)),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[&'static str],
+        operand: fn(&mut Parser) -> Result<Expr, String>,
+    ) -> Result<Expr, String> {
+        let mut lhs = operand(self)?;
+        while let Some(op) = self.eat_op(ops) {
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(operand(self)?));
+        }
+        Ok(lhs)
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        self.binary(&["||"], |p| p.binary(&["&&"], Parser::not))
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat_op(&["!"]).is_some() {
+            return Ok(Expr::Unary("!", Box::new(self.not()?)));
+        }
+        self.binary(&["<=", ">=", "==", "!=", "<", ">"], |p| {
+            p.binary(&["+", "-"], |p| p.binary(&["*", "/", "%"], Parser::atom))
+        })
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Num(v)) => Ok(Expr::Num(v)),
+            Some(Token::Ident(name)) => Ok(Expr::Field(name)),
+            Some(Token::Op("-")) => Ok(Expr::Unary("-", Box::new(self.atom()?))),
+            Some(Token::LParen) => {
+                let e = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(e)
+            }
+            token => Err(format!("Unexpected {:?}", token)),
+        }
+    }
+
+    fn query(&mut self) -> Result<Query, String> {
+        let aggregate = match (self.peek(), self.tokens.get(self.pos + 1)) {
+            (Some(Token::Ident(name)), next) => match (name.as_str(), next) {
+                ("sum", Some(Token::LParen)) => Some(Aggregate::Sum),
+                ("max", Some(Token::LParen)) => Some(Aggregate::Max),
+                ("min", Some(Token::LParen)) => Some(Aggregate::Min),
+                ("count", _) => Some(Aggregate::Count),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let query = match aggregate {
+            None => Query::Filter(self.expr()?),
+            Some(aggregate) => {
+                self.pos += 1;
+                let expr = match aggregate {
+                    // `count` and `count()` take no argument
+                    Aggregate::Count => {
+                        if self.peek() == Some(&Token::LParen) {
+                            self.expect(Token::LParen)?;
+                            self.expect(Token::RParen)?;
+                        }
+                        None
+                    }
+                    _ => {
+                        self.expect(Token::LParen)?;
+                        let e = self.expr()?;
+                        self.expect(Token::RParen)?;
+                        Some(e)
+                    }
+                };
+                let filter = match self.eat_keyword("where") {
+                    true => Some(self.expr()?),
+                    false => None,
+                };
+                let by_game = self.eat_keyword("by");
+                if by_game && !self.eat_keyword("game") {
+                    return Err("Only grouping `by game` is supported".to_string());
+                }
+                Query::Aggregate {
+                    aggregate,
+                    expr,
+                    filter,
+                    by_game,
+                }
+            }
+        };
+
+        match self.peek() {
+            None => Ok(query),
+            Some(token) => Err(format!("Unexpected {:?} after query", token)),
+        }
+    }
+}
+
+fn game_field(game: &Game, colours: &[String], name: &str) -> Result<i64, String> {
+    Ok(match name {
+        "game" | "id" => game.number,
+        "rounds" => game.rounds.len() as i64,
+        "power" => game.power(colours),
+        "round" | "cubes" => return Err(format!("{name} only makes sense with `by game`")),
+        colour if colours.iter().any(|c| c == colour) => game.max(colour),
+        _ => return Err(format!("Unknown field {name}")),
+    })
+}
+
+fn round_field(
+    game: &Game,
+    colours: &[String],
+    index: usize,
+    round: &Round,
+    name: &str,
+) -> Result<i64, String> {
+    match name {
+        "game" | "id" | "rounds" | "power" => game_field(game, colours, name),
+        "round" => Ok(index as i64 + 1),
+        "cubes" => Ok(round.total()),
+        colour if colours.iter().any(|c| c == colour) => Ok(round.count(colour)),
+        _ => Err(format!("Unknown field {name}")),
+    }
+}
+
+pub enum QueryResult {
+    Games(Vec<i64>),
+    Value(Option<i64>),
+    PerGame(Vec<(i64, Option<i64>)>),
+}
+
+impl fmt::Display for QueryResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |v: &Option<i64>| v.map_or("-".to_string(), |v| v.to_string());
+        match self {
+            QueryResult::Games(games) => write!(
+                f,
+                "{} games: {}",
+                games.len(),
+                games
+                    .iter()
+                    .map(|g| g.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            QueryResult::Value(v) => write!(f, "{}", show(v)),
+            QueryResult::PerGame(values) => write!(
+                f,
+                "{}",
+                values
+                    .iter()
+                    .map(|(game, v)| format!("Game {}: {}", game, show(v)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+        }
+    }
+}
+
+/// Fold the values of `expr` for every item matching `filter`
+fn aggregate<T>(
+    aggregate: Aggregate,
+    expr: &Option<Expr>,
+    filter: &Option<Expr>,
+    items: impl Iterator<Item = T>,
+    field: impl Fn(&T, &str) -> Result<i64, String>,
+) -> Result<Option<i64>, String> {
+    let mut acc: Option<i64> = match aggregate {
+        Aggregate::Sum | Aggregate::Count => Some(0),
+        Aggregate::Max | Aggregate::Min => None,
+    };
+    for item in items {
+        let lookup = |name: &str| field(&item, name);
+        if let Some(filter) = filter {
+            if filter.eval(&lookup)? == 0 {
+                continue;
+            }
+        }
+        let v = match expr {
+            Some(e) => e.eval(&lookup)?,
+            None => 1,
+        };
+        acc = Some(match (aggregate, acc) {
+            (Aggregate::Sum | Aggregate::Count, Some(a)) => {
+                a.checked_add(v).ok_or("Overflow in aggregate")?
+            }
+            (Aggregate::Max, Some(a)) => a.max(v),
+            (Aggregate::Min, Some(a)) => a.min(v),
+            (_, None) => v,
+        });
+    }
+    Ok(acc)
+}
+
+pub fn run(input: &str, games: &[Game]) -> Result<QueryResult, String> {
+    let mut parser = Parser {
+        tokens: tokenise(input)?,
+        pos: 0,
+    };
+    // `power` multiplies across the same colours for every game
+    let colours = colours(games);
+    match parser.query()? {
+        Query::Filter(filter) => {
+            let mut matches = Vec::<i64>::new();
+            for game in games {
+                if filter.eval(&|name| game_field(game, &colours, name))? != 0 {
+                    matches.push(game.number);
+                }
+            }
+            Ok(QueryResult::Games(matches))
+        }
+        Query::Aggregate {
+            aggregate: agg,
+            expr,
+            filter,
+            by_game: false,
+        } => Ok(QueryResult::Value(aggregate(
+            agg,
+            &expr,
+            &filter,
+            games.iter(),
+            |game, name| game_field(game, &colours, name),
+        )?)),
+        Query::Aggregate {
+            aggregate: agg,
+            expr,
+            filter,
+            by_game: true,
+        } => Ok(QueryResult::PerGame(
+            games
+                .iter()
+                .map(|game| {
+                    let value = aggregate(
+                        agg,
+                        &expr,
+                        &filter,
+                        game.rounds.iter().enumerate(),
+                        |(i, round), name| round_field(game, &colours, *i, round, name),
+                    )?;
+                    Ok((game.number, value))
+                })
+                .collect::<Result<Vec<_>, String>>()?,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_game;
+    use crate::query::{run, QueryResult};
+
+    #[test]
+    fn test_queries() {
+        let games = [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue",
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+        ]
+        .map(parse_game);
+
+        match run("red > 3 && rounds >= 3", &games).unwrap() {
+            QueryResult::Games(matches) => assert_eq!(vec![1, 3], matches),
+            _ => panic!("Expected a filter result"),
+        }
+        match run("sum(power) where blue < 5", &games).unwrap() {
+            QueryResult::Value(v) => assert_eq!(Some(12), v),
+            _ => panic!("Expected a single value"),
+        }
+        match run("max(green) by game", &games).unwrap() {
+            QueryResult::PerGame(values) => {
+                assert_eq!(vec![(1, Some(2)), (2, Some(3)), (3, Some(13))], values)
+            }
+            _ => panic!("Expected a value per game"),
+        }
+        assert!(run("sum(red / (blue - blue))", &games).is_err());
+        assert_eq!(
+            Some("Overflow in -9223372036854775808 / -1".to_string()),
+            run("(0 - 9223372036854775807 - 1) / (0-1)", &games).err()
+        );
+        assert!(run("(0 - 9223372036854775807 - 1) % (0-1)", &games).is_err());
+        assert!(run("red >", &games).is_err());
+
+        // Typos and names from the wrong scope aren't quietly zero
+        assert_eq!(
+            Some("Unknown field redd".to_string()),
+            run("count where !redd", &games).err()
+        );
+        assert!(run("max(redd) by game", &games).is_err());
+        assert_eq!(
+            Some("cubes only makes sense with `by game`".to_string()),
+            run("sum(cubes)", &games).err()
+        );
+        match run("max(cubes) by game", &games).unwrap() {
+            QueryResult::PerGame(values) => assert_eq!((1, Some(9)), values[0]),
+            _ => panic!("Expected a value per game"),
+        }
+    }
+}
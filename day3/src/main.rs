@@ -1,124 +1,763 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap},
+    env, fs,
+    ops::Range,
+};
 
-fn main() {
-    // Part 1
-    fn in_bounds(i: isize, j: isize, grid: &Vec<Vec<char>>) -> bool {
-        0 <= i
-            && i < grid.len().try_into().unwrap()
-            && 0 <= j
-            && j < grid[0].len().try_into().unwrap()
-    }
-
-    fn check_symbol_adjacent(
-        i: isize,
-        j: isize,
-        length: isize,
-        engine: &Vec<Vec<char>>,
-        gears: &mut HashMap<(usize, usize), Vec<i32>>,
-        buffer: &Vec<char>,
-    ) -> bool {
-        // i & j are the final position of a set of characters making up the overall number
-        // therefore we must also know the length of the set, so we can search the entire space
-        let dirs: Vec<(isize, isize)> = vec![
-            (-1, 0),
-            (0, -1),
-            (1, 0),
-            (0, 1),
-            (-1, -1),
-            (-1, 1),
-            (1, -1),
-            (1, 1),
-        ];
-        for offset in 0..length {
-            for (i_dir, j_dir) in &dirs {
-                if in_bounds(i + i_dir, j + j_dir - offset, &engine) {
-                    let i_off: usize = (i + i_dir).try_into().unwrap();
-                    let j_off: usize = (j + j_dir - offset).try_into().unwrap();
-                    if !engine[i_off][j_off].is_alphanumeric() && engine[i_off][j_off] != '.' {
-                        if engine[i_off][j_off] == '*' {
-                            // Store gear ratios for part 2
-                            let value = buffer.iter().collect::<String>().parse::<i32>().unwrap();
-                            gears
-                                .entry((i_off, j_off))
-                                .or_insert(Vec::new())
-                                .push(value);
-                        };
-                        return true;
+#[derive(Debug)]
+struct Number {
+    value: i32,
+    row: usize,
+    col_span: Range<usize>,
+}
+
+#[derive(Debug)]
+struct Symbol {
+    ch: char,
+    pos: (usize, usize),
+}
+
+/// The engine schematic, with every number and symbol, and which of them touch.
+/// Adjacency is kept in both directions as indices into `numbers` and `symbols`.
+struct Schematic {
+    engine: Vec<Vec<char>>,
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    number_symbols: Vec<Vec<usize>>,
+    symbol_numbers: Vec<Vec<usize>>,
+}
+
+fn in_bounds(i: isize, j: isize, grid: &[Vec<char>]) -> bool {
+    0 <= i && i < grid.len().try_into().unwrap() && 0 <= j && j < grid[0].len().try_into().unwrap()
+}
+
+fn is_symbol(c: char) -> bool {
+    !c.is_alphanumeric() && c != '.'
+}
+
+impl Schematic {
+    fn parse(input: &str) -> Schematic {
+        // Load input into 2D vec
+        let engine = input
+            .lines()
+            .map(|line| line.chars().collect())
+            .collect::<Vec<Vec<char>>>();
+
+        let mut numbers = Vec::<Number>::new();
+        let mut symbols = Vec::<Symbol>::new();
+        for (i, row) in engine.iter().enumerate() {
+            let mut j = 0;
+            while j < row.len() {
+                if row[j].is_ascii_digit() {
+                    // Numbers cannot cross lines, so run to the first non-digit (or the end of the line)
+                    let start = j;
+                    while j < row.len() && row[j].is_ascii_digit() {
+                        j += 1;
                     }
+                    numbers.push(Number {
+                        value: row[start..j].iter().collect::<String>().parse().unwrap(),
+                        row: i,
+                        col_span: start..j,
+                    });
+                    continue;
+                }
+                if is_symbol(row[j]) {
+                    symbols.push(Symbol {
+                        ch: row[j],
+                        pos: (i, j),
+                    });
+                }
+                j += 1;
+            }
+        }
+
+        let symbol_index = symbols
+            .iter()
+            .enumerate()
+            .map(|(s, symbol)| (symbol.pos, s))
+            .collect::<HashMap<(usize, usize), usize>>();
+
+        let mut number_symbols = vec![Vec::<usize>::new(); numbers.len()];
+        let mut symbol_numbers = vec![Vec::<usize>::new(); symbols.len()];
+        for (n, number) in numbers.iter().enumerate() {
+            for pos in Schematic::neighbours(number, &engine) {
+                if let Some(&s) = symbol_index.get(&pos) {
+                    number_symbols[n].push(s);
+                    symbol_numbers[s].push(n);
+                }
+            }
+        }
+
+        Schematic {
+            engine,
+            numbers,
+            symbols,
+            number_symbols,
+            symbol_numbers,
+        }
+    }
+
+    /// Every cell touching the number (including diagonals), each listed once
+    fn neighbours(number: &Number, engine: &[Vec<char>]) -> Vec<(usize, usize)> {
+        let i = number.row as isize;
+        let (start, end) = (number.col_span.start as isize, number.col_span.end as isize);
+        let mut cells = Vec::<(usize, usize)>::new();
+        for i_off in i - 1..=i + 1 {
+            for j_off in start - 1..=end {
+                let inside_number = i_off == i && start <= j_off && j_off < end;
+                if !inside_number && in_bounds(i_off, j_off, engine) {
+                    cells.push((i_off as usize, j_off as usize));
+                }
+            }
+        }
+        cells
+    }
+
+    /// Numbers adjacent to at least one symbol
+    fn part_numbers(&self) -> impl Iterator<Item = &Number> + '_ {
+        self.numbers
+            .iter()
+            .zip(&self.number_symbols)
+            .filter(|(_, symbols)| !symbols.is_empty())
+            .map(|(number, _)| number)
+    }
+
+    /// Values of the numbers adjacent to each matching symbol, keyed by the symbol's position
+    fn symbol_values(&self, matches: impl Fn(char) -> bool) -> HashMap<(usize, usize), Vec<i32>> {
+        self.symbols
+            .iter()
+            .zip(&self.symbol_numbers)
+            .filter(|(symbol, _)| matches(symbol.ch))
+            .map(|(symbol, numbers)| {
+                (
+                    symbol.pos,
+                    numbers.iter().map(|&n| self.numbers[n].value).collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// A group of part numbers joined through their symbols, where touching symbols also join up
+#[derive(Debug)]
+struct Machine {
+    numbers: Vec<usize>,
+    symbols: Vec<usize>,
+    // Inclusive corners of the bounding box, as (row, col)
+    top_left: (usize, usize),
+    bottom_right: (usize, usize),
+    total: i64,
+    symbol_set: BTreeSet<char>,
+}
+
+struct Machines {
+    machines: Vec<Machine>,
+    // Which machine covers each cell holding one of its numbers or symbols
+    cell_machine: HashMap<(usize, usize), usize>,
+}
+
+impl Machines {
+    fn containing(&self, pos: (usize, usize)) -> Option<&Machine> {
+        self.cell_machine.get(&pos).map(|&m| &self.machines[m])
+    }
+}
+
+/// Union-find root, halving the path on the way up
+fn find(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+impl Schematic {
+    /// Connected components of the adjacency graph. Nodes are numbers then symbols (offset by
+    /// the number count), with an edge for each number-symbol and symbol-symbol neighbour.
+    fn machines(&self) -> Machines {
+        let n_numbers = self.numbers.len();
+        let mut parent = (0..n_numbers + self.symbols.len()).collect::<Vec<usize>>();
+        let mut union = |a: usize, b: usize| {
+            let (root_a, root_b) = (find(&mut parent, a), find(&mut parent, b));
+            parent[root_a] = root_b;
+        };
+
+        for (n, symbols) in self.number_symbols.iter().enumerate() {
+            for s in symbols {
+                union(n, n_numbers + s);
+            }
+        }
+        let symbol_index = self
+            .symbols
+            .iter()
+            .enumerate()
+            .map(|(s, symbol)| (symbol.pos, s))
+            .collect::<HashMap<(usize, usize), usize>>();
+        for (s, symbol) in self.symbols.iter().enumerate() {
+            let (i, j) = symbol.pos;
+            // Only look forwards (right and down), as the other direction is found from the other symbol
+            for (i_off, j_off) in [
+                (i, j + 1),
+                (i + 1, j.wrapping_sub(1)),
+                (i + 1, j),
+                (i + 1, j + 1),
+            ] {
+                if let Some(&other) = symbol_index.get(&(i_off, j_off)) {
+                    union(n_numbers + s, n_numbers + other);
                 }
             }
         }
-        false
-    }
-
-    // Load input into 2D vec
-    let engine = include_str!("day3.txt")
-        .lines()
-        .map(|line| line.chars().collect())
-        .collect::<Vec<Vec<char>>>();
-
-    // Scan through arr, checking each number for an adjacent symbol
-    let mut total = 0;
-    let mut buffer = Vec::<char>::new();
-    let mut gears = HashMap::<(usize, usize), Vec<i32>>::new(); // (Part 2)
-
-    for i in 0..engine.len() {
-        for j in 0..engine[0].len() {
-            if engine[i][j].is_ascii_digit() {
-                buffer.push(engine[i][j]);
-            } else if !buffer.is_empty() {
-                // Evaluate buffer
-                if check_symbol_adjacent(
-                    i.try_into().unwrap(),
-                    (j - 1).try_into().unwrap(),
-                    buffer.len().try_into().unwrap(),
-                    &engine,
-                    &mut gears,
-                    &buffer,
-                ) {
-                    // This number is valid, so empty the buffer, and add the value to the total
-                    total += buffer
-                        .drain(0..)
-                        .collect::<String>()
-                        .parse::<i32>()
-                        .unwrap();
-                } else {
-                    // Empty buffer
-                    buffer.drain(0..);
+
+        let mut machines = Vec::<Machine>::new();
+        let mut root_machine = HashMap::<usize, usize>::new();
+        let mut cell_machine = HashMap::<(usize, usize), usize>::new();
+        for (n, number) in self.numbers.iter().enumerate() {
+            // Numbers without symbols aren't part of any machine
+            if self.number_symbols[n].is_empty() {
+                continue;
+            }
+            let root = find(&mut parent, n);
+            let m = *root_machine.entry(root).or_insert_with(|| {
+                machines.push(Machine {
+                    numbers: Vec::new(),
+                    symbols: Vec::new(),
+                    top_left: (usize::MAX, usize::MAX),
+                    bottom_right: (0, 0),
+                    total: 0,
+                    symbol_set: BTreeSet::new(),
+                });
+                machines.len() - 1
+            });
+            let machine = &mut machines[m];
+            machine.numbers.push(n);
+            machine.total += i64::from(number.value);
+            for j in number.col_span.clone() {
+                cell_machine.insert((number.row, j), m);
+            }
+            machine.top_left.0 = machine.top_left.0.min(number.row);
+            machine.top_left.1 = machine.top_left.1.min(number.col_span.start);
+            machine.bottom_right.0 = machine.bottom_right.0.max(number.row);
+            machine.bottom_right.1 = machine.bottom_right.1.max(number.col_span.end - 1);
+        }
+        for (s, symbol) in self.symbols.iter().enumerate() {
+            // Skip symbols which only touch other lone symbols
+            let Some(&m) = root_machine.get(&find(&mut parent, n_numbers + s)) else {
+                continue;
+            };
+            let machine = &mut machines[m];
+            machine.symbols.push(s);
+            machine.symbol_set.insert(symbol.ch);
+            cell_machine.insert(symbol.pos, m);
+            machine.top_left.0 = machine.top_left.0.min(symbol.pos.0);
+            machine.top_left.1 = machine.top_left.1.min(symbol.pos.1);
+            machine.bottom_right.0 = machine.bottom_right.0.max(symbol.pos.0);
+            machine.bottom_right.1 = machine.bottom_right.1.max(symbol.pos.1);
+        }
+
+        Machines {
+            machines,
+            cell_machine,
+        }
+    }
+
+    fn describe(&self, machine: &Machine) -> String {
+        format!(
+            "{} parts from ({}, {}) to ({}, {}), total {}, symbols {}",
+            machine.numbers.len(),
+            machine.top_left.0,
+            machine.top_left.1,
+            machine.bottom_right.0,
+            machine.bottom_right.1,
+            machine.total,
+            machine.symbol_set.iter().collect::<String>(),
+        )
+    }
+}
+
+/// How many numbers must touch a symbol for it to be a gear
+#[derive(Debug, PartialEq)]
+enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    Between(usize, usize),
+}
+
+#[derive(Debug, PartialEq)]
+enum Reduction {
+    Product,
+    Sum,
+    Max,
+}
+
+/// Which symbols are gears and how their ratio is found, written `<symbols>:<arity>:<reduction>`.
+/// `*:2:product` is the puzzle's rule. Symbols may be `any`, arity may be `2`, `2+` or `2..4`
+/// (inclusive), and the reduction is one of `product`, `sum` or `max`.
+#[derive(Debug, PartialEq)]
+struct GearRule {
+    symbols: Option<Vec<char>>,
+    arity: Arity,
+    reduction: Reduction,
+}
+
+impl GearRule {
+    fn from_str(s: &str) -> Result<GearRule, String> {
+        // Split from the right, so `:` can itself be a gear symbol
+        let mut parts = s.rsplitn(3, ':');
+        let (reduction, arity, symbols) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(r), Some(a), Some(s)) if !s.is_empty() => (r, a, s),
+            _ => {
+                return Err(format!(
+                    "Expected <symbols>:<arity>:<reduction>, found '{s}'"
+                ))
+            }
+        };
+        let count = |n: &str| {
+            n.parse::<usize>()
+                .map_err(|_| format!("Bad arity '{arity}' in gear rule '{s}'"))
+        };
+        Ok(GearRule {
+            symbols: match symbols {
+                "any" => None,
+                _ => Some(symbols.chars().collect()),
+            },
+            arity: if let Some(min) = arity.strip_suffix('+') {
+                Arity::AtLeast(count(min)?)
+            } else if let Some((min, max)) = arity.split_once("..") {
+                Arity::Between(count(min)?, count(max)?)
+            } else {
+                Arity::Exactly(count(arity)?)
+            },
+            reduction: match reduction {
+                "product" => Reduction::Product,
+                "sum" => Reduction::Sum,
+                "max" => Reduction::Max,
+                _ => return Err(format!("Bad reduction '{reduction}' in gear rule '{s}'")),
+            },
+        })
+    }
+
+    fn is_gear_symbol(&self, ch: char) -> bool {
+        self.symbols
+            .as_ref()
+            .is_none_or(|symbols| symbols.contains(&ch))
+    }
+
+    /// The gear's ratio, or None if the symbol has the wrong number of neighbours to be a gear
+    fn ratio(&self, values: &[i32]) -> Option<i64> {
+        let n = values.len();
+        let is_gear = match self.arity {
+            Arity::Exactly(count) => n == count,
+            Arity::AtLeast(min) => n >= min,
+            Arity::Between(min, max) => min <= n && n <= max,
+        };
+        if !is_gear {
+            return None;
+        }
+        let values = values.iter().map(|&v| i64::from(v));
+        match self.reduction {
+            Reduction::Product => Some(values.product()),
+            Reduction::Sum => Some(values.sum()),
+            Reduction::Max => values.max(),
+        }
+    }
+
+    fn total(&self, schematic: &Schematic) -> i64 {
+        schematic
+            .symbol_values(|ch| self.is_gear_symbol(ch))
+            .values()
+            .filter_map(|ratios| self.ratio(ratios))
+            .sum()
+    }
+}
+
+/// What to highlight each cell of the engine as, holding the index of its number or symbol
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    Part(usize),
+    NonPart(usize),
+    Gear(usize, i64),
+    Symbol(usize),
+    LoneSymbol(usize),
+    Empty,
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+
+impl Cell {
+    fn ansi_colour(&self) -> Option<&'static str> {
+        match self {
+            Cell::Part(_) => Some("\x1b[32m"),
+            Cell::NonPart(_) => Some("\x1b[2m"),
+            Cell::Gear(..) => Some("\x1b[1;33m"),
+            Cell::Symbol(_) => Some("\x1b[36m"),
+            Cell::LoneSymbol(_) => Some("\x1b[1;31m"),
+            Cell::Empty => None,
+        }
+    }
+
+    fn html_class(&self) -> Option<&'static str> {
+        match self {
+            Cell::Part(_) => Some("part"),
+            Cell::NonPart(_) => Some("non-part"),
+            Cell::Gear(..) => Some("gear"),
+            Cell::Symbol(_) => Some("symbol"),
+            Cell::LoneSymbol(_) => Some("lone"),
+            Cell::Empty => None,
+        }
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Schematic {
+    fn cells(&self, rule: &GearRule) -> Vec<Vec<Cell>> {
+        let mut cells = self
+            .engine
+            .iter()
+            .map(|row| vec![Cell::Empty; row.len()])
+            .collect::<Vec<Vec<Cell>>>();
+        for (n, number) in self.numbers.iter().enumerate() {
+            for j in number.col_span.clone() {
+                cells[number.row][j] = match self.number_symbols[n].is_empty() {
+                    true => Cell::NonPart(n),
+                    false => Cell::Part(n),
+                };
+            }
+        }
+        for (s, symbol) in self.symbols.iter().enumerate() {
+            let values = self.symbol_numbers[s]
+                .iter()
+                .map(|&n| self.numbers[n].value)
+                .collect::<Vec<i32>>();
+            let ratio = match rule.is_gear_symbol(symbol.ch) {
+                true => rule.ratio(&values),
+                false => None,
+            };
+            cells[symbol.pos.0][symbol.pos.1] = match ratio {
+                Some(ratio) => Cell::Gear(s, ratio),
+                None if values.is_empty() => Cell::LoneSymbol(s),
+                None => Cell::Symbol(s),
+            };
+        }
+        cells
+    }
+
+    /// Hover text for a highlighted cell
+    fn tooltip(&self, cell: Cell) -> String {
+        let symbol_values = |s: usize| {
+            self.symbol_numbers[s]
+                .iter()
+                .map(|&n| self.numbers[n].value.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        match cell {
+            Cell::Part(n) => format!(
+                "part {}, touching {}",
+                self.numbers[n].value,
+                self.number_symbols[n]
+                    .iter()
+                    .map(|&s| {
+                        let symbol = &self.symbols[s];
+                        format!("{} at ({}, {})", symbol.ch, symbol.pos.0, symbol.pos.1)
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Cell::NonPart(n) => format!("{} touches no symbol", self.numbers[n].value),
+            Cell::Gear(s, ratio) => format!("gear ratio {} from {}", ratio, symbol_values(s)),
+            Cell::Symbol(s) => format!("not a gear, touching {}", symbol_values(s)),
+            Cell::LoneSymbol(_) => "touches no part".to_string(),
+            Cell::Empty => String::new(),
+        }
+    }
+
+    /// The engine coloured for a terminal, followed by each gear's ratio
+    fn render_ansi(&self, rule: &GearRule) -> String {
+        let cells = self.cells(rule);
+        let mut out = String::new();
+        for (row, row_cells) in self.engine.iter().zip(&cells) {
+            // Only switch colour where it changes, rather than around every character. Codes add
+            // to whatever's already set, so reset before each change or bold carries over.
+            let mut current = None;
+            for (ch, cell) in row.iter().zip(row_cells) {
+                let colour = cell.ansi_colour();
+                if colour != current {
+                    if current.is_some() {
+                        out += ANSI_RESET;
+                    }
+                    out += colour.unwrap_or("");
+                    current = colour;
                 }
+                out.push(*ch);
+            }
+            if current.is_some() {
+                out += ANSI_RESET;
             }
+            out.push('\n');
         }
-        // End of line, so evaluate the buffer if not empty because numbers cannot cross-lines
-        if !buffer.is_empty() {
-            // Evaluate buffer
-            if check_symbol_adjacent(
-                i.try_into().unwrap(),
-                (engine[0].len() - 1).try_into().unwrap(),
-                buffer.len().try_into().unwrap(),
-                &engine,
-                &mut gears,
-                &buffer,
-            ) {
-                // This number is valid, so empty the buffer, and add the value to the total
-                total += buffer
-                    .drain(0..)
-                    .collect::<String>()
-                    .parse::<i32>()
-                    .unwrap();
+        for symbol in &self.symbols {
+            if let Cell::Gear(s, ratio) = cells[symbol.pos.0][symbol.pos.1] {
+                out += &format!(
+                    "{} at ({}, {}): {}\n",
+                    symbol.ch,
+                    symbol.pos.0,
+                    symbol.pos.1,
+                    self.tooltip(Cell::Gear(s, ratio))
+                );
+            }
+        }
+        out
+    }
+
+    /// A standalone page of the engine, with a tooltip on every number and symbol
+    fn render_html(&self, rule: &GearRule) -> String {
+        let cells = self.cells(rule);
+        let mut grid = String::new();
+        for (row, row_cells) in self.engine.iter().zip(&cells) {
+            let mut j = 0;
+            while j < row.len() {
+                let cell = row_cells[j];
+                // Keep the digits of a number together under one tooltip
+                let mut end = j + 1;
+                while end < row.len()
+                    && row_cells[end] == cell
+                    && matches!(cell, Cell::Part(_) | Cell::NonPart(_))
+                {
+                    end += 1;
+                }
+                let text = escape_html(&row[j..end].iter().collect::<String>());
+                match cell.html_class() {
+                    Some(class) => {
+                        grid += &format!(
+                            "<span class=\"{}\" title=\"{}\">{}</span>",
+                            class,
+                            escape_html(&self.tooltip(cell)),
+                            text
+                        )
+                    }
+                    None => grid += &text,
+                }
+                j = end;
             }
+            grid.push('\n');
         }
-        buffer.drain(0..);
+
+        format!(
+            "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Engine schematic</title>
+<style>
+body {{ background: #10101a; color: #555; }}
+pre {{ font-size: 14px; line-height: 1.2; }}
+span[title] {{ cursor: help; }}
+.part {{ color: #4c4; }}
+.non-part {{ color: #888; text-decoration: line-through; }}
+.gear {{ color: #ff0; font-weight: bold; background: #442; }}
+.symbol {{ color: #4cc; }}
+.lone {{ color: #f44; font-weight: bold; }}
+</style>
+</head>
+<body>
+<pre>
+{}</pre>
+</body>
+</html>
+",
+            grid
+        )
     }
+}
+
+fn main() {
+    let schematic = Schematic::parse(include_str!("day3.txt"));
 
     // Part 1
+    let total: i32 = schematic.part_numbers().map(|number| number.value).sum();
+
     println!("{}", total);
 
-    // Part 2
-    let gear_ratio_total: i32 = gears
-        .values()
-        .filter(|ratios| ratios.len() == 2)
-        .map(|ratios| ratios.iter().product::<i32>())
-        .sum();
+    // Part 2 (for the puzzle's gears, unless other rules are given with `--gear <rule>`)
+    // `--machines` lists every machine, and `--machine-at <row>,<col>` finds the one covering a cell
+    // `--ansi` draws the engine in the terminal and `--html <file>` writes it out as a web page
+    let mut rules = Vec::<GearRule>::new();
+    let mut list_machines = false;
+    let mut cells = Vec::<(usize, usize)>::new();
+    let mut ansi = false;
+    let mut html_path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--gear" => rules.push(
+                GearRule::from_str(&args.next().expect("--gear needs a rule"))
+                    .unwrap_or_else(|e| panic!("{e}")),
+            ),
+            "--machines" => list_machines = true,
+            "--ansi" => ansi = true,
+            "--html" => html_path = Some(args.next().expect("--html needs a path")),
+            "--machine-at" => {
+                let cell = args.next().expect("--machine-at needs a cell");
+                let (row, col) = cell
+                    .split_once(',')
+                    .unwrap_or_else(|| panic!("Expected <row>,<col>, found {cell}"));
+                cells.push((row.trim().parse().unwrap(), col.trim().parse().unwrap()));
+            }
+            _ => panic!("Unknown argument {arg}"),
+        }
+    }
+    if rules.is_empty() {
+        rules.push(GearRule::from_str("*:2:product").unwrap());
+    }
+
+    for rule in &rules {
+        println!("{}", rule.total(&schematic));
+    }
+
+    // Machines
+    if list_machines || !cells.is_empty() {
+        let machines = schematic.machines();
+        if list_machines {
+            for (m, machine) in machines.machines.iter().enumerate() {
+                println!("Machine {}: {}", m, schematic.describe(machine));
+            }
+        }
+        for (row, col) in cells {
+            match machines.containing((row, col)) {
+                Some(machine) => println!("({row}, {col}): {}", schematic.describe(machine)),
+                None => println!("({row}, {col}): not part of a machine"),
+            }
+        }
+    }
+
+    // Pictures, highlighting gears from the first rule
+    if ansi {
+        print!("{}", schematic.render_ansi(&rules[0]));
+    }
+    if let Some(path) = html_path {
+        fs::write(&path, schematic.render_html(&rules[0]))
+            .unwrap_or_else(|e| panic!("Couldn't write {path}: {e}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arity, Cell, GearRule, Reduction, Schematic};
+
+    #[test]
+    fn test_number_touching_two_gears() {
+        let schematic = Schematic::parse(
+            "467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..
+*...*.....
+2...3.....",
+        );
+        assert_eq!(
+            4361 + 2 + 3,
+            schematic.part_numbers().map(|n| n.value).sum::<i32>()
+        );
+
+        // 664 touches both of the last two gears, so counts towards each
+        let gears = schematic.symbol_values(|ch| ch == '*');
+        assert_eq!(Some(&vec![664, 2]), gears.get(&(10, 0)));
+        assert_eq!(Some(&vec![664, 598, 3]), gears.get(&(10, 4)));
+
+        let puzzle = GearRule::from_str("*:2:product").unwrap();
+        assert_eq!(467835 + 664 * 2, puzzle.total(&schematic));
+        let triples = GearRule::from_str("*:3+:sum").unwrap();
+        assert_eq!(664 + 598 + 3, triples.total(&schematic));
+        let any = GearRule::from_str("any:1..1:max").unwrap();
+        assert_eq!(633 + 617 + 592 + 664, any.total(&schematic));
+    }
+
+    #[test]
+    fn test_machines() {
+        // The symbols of the bottom machine chain across three rows through `#` and `+`
+        let schematic = Schematic::parse(
+            "12*34.9.
+.......$
+5.......
+.#....7.
+..+..*..
+.....8..",
+        );
+        let machines = schematic.machines();
+        assert_eq!(4, machines.machines.len());
 
-    println!("{}", gear_ratio_total);
+        let top = machines.containing((0, 4)).unwrap();
+        assert_eq!(46, top.total);
+        assert_eq!(((0, 0), (0, 4)), (top.top_left, top.bottom_right));
+
+        let chained = machines.containing((4, 2)).unwrap();
+        assert_eq!(5, chained.total);
+        assert_eq!(((2, 0), (4, 2)), (chained.top_left, chained.bottom_right));
+        assert_eq!("#+", chained.symbol_set.iter().collect::<String>());
+
+        assert_eq!(9, machines.containing((1, 7)).unwrap().total);
+        assert_eq!(15, machines.containing((3, 6)).unwrap().total);
+        assert!(machines.containing((2, 3)).is_none());
+    }
+
+    #[test]
+    fn test_render() {
+        let schematic = Schematic::parse("1*2..5\n..&...\n.....<");
+        let rule = GearRule::from_str("*:2:product").unwrap();
+        let cells = schematic.cells(&rule);
+        assert_eq!(
+            vec![
+                Cell::Part(0),
+                Cell::Gear(0, 2),
+                Cell::Part(1),
+                Cell::Empty,
+                Cell::Empty,
+                Cell::NonPart(2)
+            ],
+            cells[0]
+        );
+        assert_eq!(Cell::Symbol(1), cells[1][2]);
+        assert_eq!(Cell::LoneSymbol(2), cells[2][5]);
+
+        let html = schematic.render_html(&rule);
+        assert!(html.contains("<span class=\"gear\" title=\"gear ratio 2 from 1, 2\">*</span>"));
+        assert!(html.contains("<span class=\"lone\" title=\"touches no part\">&lt;</span>"));
+        assert!(html.contains("<span class=\"non-part\" title=\"5 touches no symbol\">5</span>"));
+
+        let ansi = schematic.render_ansi(&rule);
+        assert_eq!(
+            "\x1b[32m1\x1b[0m\x1b[1;33m*\x1b[0m\x1b[32m2\x1b[0m..\x1b[2m5\x1b[0m\n\
+             ..\x1b[36m&\x1b[0m...\n\
+             .....\x1b[1;31m<\x1b[0m\n\
+             * at (0, 1): gear ratio 2 from 1, 2\n",
+            ansi
+        );
+    }
+
+    #[test]
+    fn test_gear_rule_from_str() {
+        assert_eq!(
+            Ok(GearRule {
+                symbols: Some(vec![':', '#']),
+                arity: Arity::Between(2, 4),
+                reduction: Reduction::Max,
+            }),
+            GearRule::from_str(":#:2..4:max")
+        );
+        assert!(GearRule::from_str("*:two:product").is_err());
+        assert!(GearRule::from_str("*:2:mean").is_err());
+        assert!(GearRule::from_str("2:product").is_err());
+    }
 }
@@ -1,50 +1,389 @@
-use std::{cmp::min, collections::HashSet};
+use std::{cmp::Ordering, env};
 
-fn main() {
-    fn create_split_hashset(numbers: &str) -> HashSet<u32> {
-        HashSet::<u32>::from_iter(
-            numbers
-                .split_ascii_whitespace()
-                .map(|v| v.parse::<u32>().unwrap())
-                .collect::<Vec<u32>>(),
-        )
+// Bitsets cover card numbers below 64 * BITSET_WORDS
+const BITSET_WORDS: usize = 4;
+
+/// The winning numbers and the numbers we hold. Small numbers are kept as bitsets so matches
+/// are just a popcount, falling back to sorted (deduplicated) lists for anything larger.
+#[derive(Debug, PartialEq)]
+enum Card {
+    Bits {
+        winning: [u64; BITSET_WORDS],
+        ours: [u64; BITSET_WORDS],
+    },
+    Sorted {
+        winning: Vec<u32>,
+        ours: Vec<u32>,
+    },
+}
+
+fn parse_numbers(numbers: &str) -> impl Iterator<Item = u32> + '_ {
+    numbers
+        .split_ascii_whitespace()
+        .map(|v| v.parse::<u32>().unwrap())
+}
+
+/// Set a bit for every number, or return false if one is too big for the bitset
+fn fill_bitset(numbers: &str, bits: &mut [u64; BITSET_WORDS]) -> bool {
+    for v in parse_numbers(numbers) {
+        let v = v as usize;
+        if v >= 64 * BITSET_WORDS {
+            return false;
+        }
+        bits[v / 64] |= 1 << (v % 64);
     }
+    true
+}
 
-    fn count_matches(line: &str) -> u32 {
+fn sorted_numbers(numbers: &str) -> Vec<u32> {
+    let mut sorted = parse_numbers(numbers).collect::<Vec<u32>>();
+    sorted.sort_unstable();
+    sorted.dedup();
+    sorted
+}
+
+impl Card {
+    fn parse(line: &str) -> Card {
         let (_, scorecard) = line.split_once(": ").unwrap();
         let (winning_numbers, our_numbers) = scorecard.split_once(" | ").unwrap();
-        let winning_set = create_split_hashset(winning_numbers);
-        let our_set = create_split_hashset(our_numbers);
-        winning_set
-            .intersection(&our_set)
-            .count()
-            .try_into()
-            .unwrap()
+        let mut winning = [0_u64; BITSET_WORDS];
+        let mut ours = [0_u64; BITSET_WORDS];
+        if fill_bitset(winning_numbers, &mut winning) && fill_bitset(our_numbers, &mut ours) {
+            Card::Bits { winning, ours }
+        } else {
+            Card::Sorted {
+                winning: sorted_numbers(winning_numbers),
+                ours: sorted_numbers(our_numbers),
+            }
+        }
+    }
+
+    fn matches(&self) -> u32 {
+        match self {
+            Card::Bits { winning, ours } => winning
+                .iter()
+                .zip(ours)
+                .map(|(w, o)| (w & o).count_ones())
+                .sum(),
+            Card::Sorted { winning, ours } => {
+                // Walk both lists together, like the merge step of a merge sort
+                let (mut i, mut j, mut count) = (0, 0, 0);
+                while i < winning.len() && j < ours.len() {
+                    match winning[i].cmp(&ours[j]) {
+                        Ordering::Less => i += 1,
+                        Ordering::Greater => j += 1,
+                        Ordering::Equal => {
+                            count += 1;
+                            i += 1;
+                            j += 1;
+                        }
+                    }
+                }
+                count
+            }
+        }
+    }
+}
+
+fn count_matches(line: &str) -> u32 {
+    Card::parse(line).matches()
+}
+
+/// What to do when a card wins copies of cards past the end of the table
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OffTable {
+    // The puzzle promises this never happens, so just drop the missing cards
+    Ignore,
+    Error,
+}
+
+/// Points for a card with the given number of matches
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scoring {
+    Linear,
+    // The puzzle doubles for every match after the first, i.e. base 2
+    Exponential(u128),
+}
+
+impl Scoring {
+    fn from_str(s: &str) -> Scoring {
+        match s.split_once(':') {
+            None if s == "linear" => Scoring::Linear,
+            None if s == "exp" => Scoring::Exponential(2),
+            Some(("exp", base)) => Scoring::Exponential(base.parse().unwrap()),
+            _ => panic!("Expected linear, exp or exp:<base>, found {s}"),
+        }
     }
 
-    // Part 1:
-    let winnings: u32 = include_str!("day4.txt")
+    fn score(&self, matches: u32) -> Option<u128> {
+        match (self, matches) {
+            (_, 0) => Some(0),
+            (Scoring::Linear, m) => Some(u128::from(m)),
+            (Scoring::Exponential(base), m) => base.checked_pow(m - 1),
+        }
+    }
+}
+
+/// Which cards a win hands out copies of
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CascadeRule {
+    // One copy of each of the next <matches> cards (the puzzle)
+    Next,
+    // One copy of each of the previous <matches> cards
+    Previous,
+    // <matches> copies of each of the next <matches> cards
+    Weighted,
+}
+
+impl CascadeRule {
+    fn from_str(s: &str) -> CascadeRule {
+        match s {
+            "next" => CascadeRule::Next,
+            "previous" => CascadeRule::Previous,
+            "weighted" => CascadeRule::Weighted,
+            _ => panic!("Expected next, previous or weighted, found {s}"),
+        }
+    }
+}
+
+struct Cascade {
+    counts: Vec<u128>,
+    /// For each card, how many of its copies were won from each other card
+    sources: Vec<Vec<(usize, u128)>>,
+}
+
+impl Cascade {
+    fn total(&self) -> Result<u128, String> {
+        self.counts
+            .iter()
+            .try_fold(0_u128, |acc, c| acc.checked_add(*c))
+            .ok_or_else(|| "Total number of scorecards overflows u128".to_string())
+    }
+}
+
+fn cascade(matches: &[u32], rule: CascadeRule, off_table: OffTable) -> Result<Cascade, String> {
+    let n = matches.len();
+    let mut counts = vec![1_u128; n];
+    let mut sources = vec![Vec::<(usize, u128)>::new(); n];
+
+    // Luckily, a scorecard win can only give you more scorecards in one direction, so we can
+    // start from the other end (from the back when winning previous cards).
+    let order: Box<dyn Iterator<Item = usize>> = match rule {
+        CascadeRule::Previous => Box::new((0..n).rev()),
+        _ => Box::new(0..n),
+    };
+    for i in order {
+        let wins = usize::try_from(matches[i]).unwrap();
+        let (targets, off) = match rule {
+            CascadeRule::Previous => (i.saturating_sub(wins)..i, wins > i),
+            _ => (i + 1..(i + 1 + wins).min(n), i + wins >= n),
+        };
+        if off && off_table == OffTable::Error {
+            return Err(format!(
+                "Card {} wins {} cards, but runs off the table of {} cards",
+                i + 1,
+                wins,
+                n
+            ));
+        }
+        let won = match rule {
+            CascadeRule::Weighted => counts[i].checked_mul(wins as u128),
+            _ => Some(counts[i]),
+        }
+        .ok_or_else(|| format!("Copies won from card {} overflow u128", i + 1))?;
+        for j in targets {
+            counts[j] = counts[j]
+                .checked_add(won)
+                .ok_or_else(|| format!("Copies of card {} overflow u128", j + 1))?;
+            sources[j].push((i, won));
+        }
+    }
+
+    Ok(Cascade { counts, sources })
+}
+
+struct Rules {
+    scoring: Scoring,
+    cascade: CascadeRule,
+    off_table: OffTable,
+}
+
+impl Rules {
+    /// Part 1 and part 2 totals
+    fn totals(&self, matches: &[u32]) -> Result<(u128, u128), String> {
+        let points = matches
+            .iter()
+            .try_fold(0_u128, |acc, m| acc.checked_add(self.scoring.score(*m)?))
+            .ok_or_else(|| "Total points overflow u128".to_string())?;
+        let cards = cascade(matches, self.cascade, self.off_table)?.total()?;
+        Ok((points, cards))
+    }
+}
+
+/// `after` compared to `before`, as `before -> after (+/-change)`
+fn describe_change(before: u128, after: u128) -> String {
+    match after >= before {
+        true => format!("{} -> {} (+{})", before, after, after - before),
+        false => format!("{} -> {} (-{})", before, after, before - after),
+    }
+}
+
+fn card_number(line: &str) -> usize {
+    let (name, _) = line.split_once(':').unwrap();
+    name.trim_start_matches("Card").trim().parse().unwrap()
+}
+
+fn main() {
+    // `--strict` refuses wins past the last card, `--provenance` explains where every copy came from.
+    // `--score linear|exp|exp:<base>` and `--cascade next|previous|weighted` swap the rules, and
+    // `--what-if "Card <n>: ..."` shows how the totals change if that card is replaced.
+    let mut rules = Rules {
+        scoring: Scoring::Exponential(2),
+        cascade: CascadeRule::Next,
+        off_table: OffTable::Ignore,
+    };
+    let mut provenance = false;
+    let mut edits = Vec::<String>::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strict" => rules.off_table = OffTable::Error,
+            "--provenance" => provenance = true,
+            "--score" => {
+                rules.scoring = Scoring::from_str(&args.next().expect("--score needs a rule"))
+            }
+            "--cascade" => {
+                rules.cascade = CascadeRule::from_str(&args.next().expect("--cascade needs a rule"))
+            }
+            "--what-if" => edits.push(args.next().expect("--what-if needs a card")),
+            _ => panic!("Unknown argument {arg}"),
+        }
+    }
+
+    let all_matches = include_str!("day4.txt")
         .lines()
         .map(count_matches)
-        .filter(|matches| matches > &0)
-        .map(|matches| 2_u32.pow(matches - 1))
-        .sum();
+        .collect::<Vec<u32>>();
 
-    println!("{}", winnings);
+    // Part 1 & Part 2:
+    let (winnings, total_scorecards) = rules.totals(&all_matches).unwrap_or_else(|e| panic!("{e}"));
 
-    // Part 2:
-    let all_scorecards = include_str!("day4.txt").lines().collect::<Vec<&str>>();
+    println!("{}", winnings);
+    println!("{:#?}", total_scorecards);
 
-    // Luckily, a scorecard win can only give you more *later* scorecards, (so we can start from the beginning).
-    let mut counts = vec![1_u32; all_scorecards.len()];
+    if provenance {
+        let cards = cascade(&all_matches, rules.cascade, rules.off_table).unwrap();
+        for (j, sources) in cards.sources.iter().enumerate() {
+            let won = sources
+                .iter()
+                .map(|(i, copies)| format!("{} from card {}", copies, i + 1))
+                .collect::<Vec<String>>();
+            println!(
+                "Card {}: {} copies (1 original{}{})",
+                j + 1,
+                cards.counts[j],
+                if won.is_empty() { "" } else { ", " },
+                won.join(", ")
+            );
+        }
+    }
 
-    for (i, &line) in all_scorecards.iter().enumerate() {
-        let winnings = count_matches(line);
-        for j in i..min(i + usize::try_from(winnings).unwrap(), all_scorecards.len()) {
-            counts[j + 1] += counts[i];
+    for edit in edits {
+        let number = card_number(&edit);
+        if number == 0 || number > all_matches.len() {
+            println!("{edit}: there is no card {number}");
+            continue;
+        }
+        let mut edited = all_matches.clone();
+        edited[number - 1] = count_matches(&edit);
+        match rules.totals(&edited) {
+            Ok((points, cards)) => println!(
+                "{edit}: points {}, cards {}",
+                describe_change(winnings, points),
+                describe_change(total_scorecards, cards)
+            ),
+            Err(e) => println!("{edit}: {e}"),
         }
     }
+}
 
-    let total_scorecards: u32 = counts.iter().sum();
-    println!("{:#?}", total_scorecards);
+#[cfg(test)]
+mod tests {
+    use crate::{cascade, Card, CascadeRule, OffTable, Rules, Scoring};
+
+    #[test]
+    fn test_matches() {
+        let card = Card::parse("Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53");
+        assert!(matches!(card, Card::Bits { .. }));
+        assert_eq!(4, card.matches());
+
+        // Too big for the bitset, and repeated numbers only match once (as with sets)
+        let card = Card::parse("Card 2: 1000 48 48 7 | 48 1000 1000 6");
+        assert!(matches!(card, Card::Sorted { .. }));
+        assert_eq!(2, card.matches());
+
+        // Both representations agree across the edges of the bitset words
+        let bits = Card::parse("Card 3: 0 63 64 127 128 255 | 255 0 64 65 200 127");
+        let sorted = Card::parse("Card 3: 0 63 64 127 128 255 256 | 255 0 64 65 200 127");
+        assert!(matches!(bits, Card::Bits { .. }));
+        assert!(matches!(sorted, Card::Sorted { .. }));
+        assert_eq!(4, bits.matches());
+        assert_eq!(4, sorted.matches());
+    }
+
+    #[test]
+    fn test_cascade() {
+        let cards = cascade(&[4, 2, 2, 1, 0, 0], CascadeRule::Next, OffTable::Ignore).unwrap();
+        assert_eq!(vec![1, 2, 4, 8, 14, 1], cards.counts);
+        assert_eq!(30, cards.total().unwrap());
+        assert_eq!(vec![(0, 1), (2, 4), (3, 8)], cards.sources[4]);
+
+        // Wins on the last cards used to index past the end of the table
+        let cards = cascade(&[0, 3, 1], CascadeRule::Next, OffTable::Ignore).unwrap();
+        assert_eq!(vec![1, 1, 2], cards.counts);
+        assert!(cascade(&[0, 3, 1], CascadeRule::Next, OffTable::Error).is_err());
+    }
+
+    #[test]
+    fn test_cascade_overflow() {
+        // Every card doubles the copies of all the cards after it
+        let matches = (0..200).rev().collect::<Vec<u32>>();
+        let cards = cascade(&matches[..128], CascadeRule::Next, OffTable::Ignore).unwrap();
+        assert_eq!(1 << 127, cards.counts[127]);
+        assert_eq!(u128::MAX, cards.total().unwrap());
+        assert!(cascade(&matches[..129], CascadeRule::Next, OffTable::Ignore).is_err());
+    }
+
+    #[test]
+    fn test_alternative_rules() {
+        let matches = [4, 2, 2, 1, 0, 0];
+        let rules = |scoring, cascade| Rules {
+            scoring,
+            cascade,
+            off_table: OffTable::Ignore,
+        };
+
+        assert_eq!(
+            Ok((13, 30)),
+            rules(Scoring::Exponential(2), CascadeRule::Next).totals(&matches)
+        );
+        assert_eq!(
+            Ok((9, 30)),
+            rules(Scoring::Linear, CascadeRule::Next).totals(&matches)
+        );
+        assert_eq!(
+            Ok((34, 30)),
+            rules(Scoring::Exponential(3), CascadeRule::Next).totals(&matches)
+        );
+
+        // Card 4 wins a card 3, both card 3s win cards 1 and 2, then the three card 2s win card 1
+        // (and card 1 runs off the table)
+        let previous = cascade(&matches, CascadeRule::Previous, OffTable::Ignore).unwrap();
+        assert_eq!(vec![6, 3, 2, 1, 1, 1], previous.counts);
+        assert!(cascade(&matches, CascadeRule::Previous, OffTable::Error).is_err());
+
+        // Card 1 gives 4 copies to each of cards 2-5, then the five card 2s give 2 x 5 to cards 3 and 4...
+        let weighted = cascade(&matches, CascadeRule::Weighted, OffTable::Ignore).unwrap();
+        assert_eq!(vec![1, 5, 15, 45, 80, 1], weighted.counts);
+    }
 }
@@ -1,5 +1,4 @@
-use std::collections::HashMap;
-use rayon::prelude::*;
+use std::{env, fmt};
 
 #[derive(Debug)]
 struct Range {
@@ -18,8 +17,13 @@ impl Range {
         }
     }
 
+    /// The first value past the end of the (half-open) source range, which can be 2⁶⁴
+    fn end(&self) -> u128 {
+        self.source as u128 + self.size as u128
+    }
+
     fn contains(&self, value: u64) -> bool {
-        self.source <= value && value <= self.source + self.size
+        self.source <= value && (value as u128) < self.end()
     }
 
     fn destination_from_source(&self, value: u64) -> u64 {
@@ -27,44 +31,270 @@ impl Range {
     }
 }
 
-fn main() {
-    fn parse_into_hashmap(map_str: &str) -> HashMap<&str, Vec<Range>> {
-        let (map_name_str, map_data) = map_str.split_once("\n").unwrap();
-        let map_name = map_name_str.split_once(" ").unwrap().0;
-        let ranges = map_data
-            .lines()
-            .map(Range::from_line)
-            .collect::<Vec<Range>>();
-        HashMap::from([(map_name, ranges)])
+/// Half-open interval of values, `start..end`. Wide enough to end at 2⁶⁴, past every u64.
+type Interval = (u128, u128);
+
+/// Push whole intervals through one map. Each interval is split at the range boundaries, so the
+/// pieces inside a range are shifted together and the pieces outside every range pass through.
+/// Empty intervals hold no values, so they're dropped rather than passed through.
+fn map_intervals(ranges: &[Range], intervals: Vec<Interval>) -> Vec<Interval> {
+    let mut mapped = Vec::<Interval>::new();
+    let mut pending = intervals
+        .into_iter()
+        .filter(|(start, end)| start < end)
+        .collect::<Vec<Interval>>();
+    for range in ranges {
+        let (range_start, range_end) = (range.source as u128, range.end());
+        let shift = |value: u128| value - range_start + range.destination as u128;
+        let mut unmapped = Vec::<Interval>::new();
+        for (start, end) in pending {
+            let (overlap_start, overlap_end) = (start.max(range_start), end.min(range_end));
+            if overlap_start >= overlap_end {
+                unmapped.push((start, end));
+                continue;
+            }
+            mapped.push((shift(overlap_start), shift(overlap_end)));
+            if start < overlap_start {
+                unmapped.push((start, overlap_start));
+            }
+            if overlap_end < end {
+                unmapped.push((overlap_end, end));
+            }
+        }
+        pending = unmapped;
+    }
+    mapped.extend(pending);
+    mapped
+}
+
+/// One `<source>-to-<destination> map:` block of the almanac
+#[derive(Debug)]
+struct Map<'a> {
+    source: &'a str,
+    destination: &'a str,
+    ranges: Vec<Range>,
+}
+
+/// Why the maps don't form a single chain between two categories
+#[derive(Debug, PartialEq)]
+enum ChainError {
+    BadHeader(String),
+    // Nothing maps on from `from`, so `to` is never reached
+    MissingStage { from: String, to: String },
+    // More than one map leaves the same category. (Matching map names by prefix used to pick one
+    // of these at random, along with others like `seedling-to-...` for `seed`.)
+    Ambiguous { source: String, maps: Vec<String> },
+    // The chain comes back round to a category it has already passed through
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::BadHeader(header) => {
+                write!(
+                    f,
+                    "Expected '<source>-to-<destination> map:', found '{header}'"
+                )
+            }
+            ChainError::MissingStage { from, to } => {
+                write!(f, "No map leads on from {from}, so {to} can't be reached")
+            }
+            ChainError::Ambiguous { source, maps } => {
+                write!(f, "More than one map from {source}: {}", maps.join(", "))
+            }
+            ChainError::Cycle(categories) => {
+                write!(f, "Maps loop round: {}", categories.join(" -> "))
+            }
+        }
+    }
+}
+
+/// Problems within a single map
+#[derive(Debug, PartialEq)]
+struct MapReport {
+    // Pairs of ranges (by index) whose sources overlap. The first listed wins in those values.
+    overlaps: Vec<(usize, usize)>,
+    // Unmapped holes between the lowest and highest mapped sources
+    gaps: Vec<Interval>,
+}
+
+impl<'a> Map<'a> {
+    fn parse(block: &'a str) -> Result<Map<'a>, ChainError> {
+        let (header, data) = block.split_once('\n').unwrap_or((block, ""));
+        let bad_header = || ChainError::BadHeader(header.to_string());
+        let (source, destination) = header
+            .trim()
+            .strip_suffix(" map:")
+            .and_then(|name| name.split_once("-to-"))
+            .filter(|(source, destination)| !source.is_empty() && !destination.is_empty())
+            .ok_or_else(bad_header)?;
+        Ok(Map {
+            source,
+            destination,
+            ranges: data
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(Range::from_line)
+                .collect(),
+        })
+    }
+
+    fn name(&self) -> String {
+        format!("{}-to-{}", self.source, self.destination)
     }
 
-    /// Recursively walk through the graph, updating the value as each range applies an offset
-    fn walk_graph(mappings: &HashMap<&str, Vec<Range>>, mut value: u64, start_node: &str) -> u64 {
-        let current_node = mappings
-            .keys()
-            .filter(|&k| k.starts_with(start_node))
-            .cloned()
-            .next();
+    fn apply(&self, value: u64) -> u64 {
+        self.ranges
+            .iter()
+            .find(|range| range.contains(value))
+            .map_or(value, |range| range.destination_from_source(value))
+    }
 
-        // If there are no more nodes, return the value!
-        if current_node.is_none() {
-            return value;
+    fn validate(&self) -> MapReport {
+        let mut overlaps = Vec::<(usize, usize)>::new();
+        for (i, a) in self.ranges.iter().enumerate() {
+            for (j, b) in self.ranges.iter().enumerate().skip(i + 1) {
+                if (a.source.max(b.source) as u128) < a.end().min(b.end()) {
+                    overlaps.push((i, j));
+                }
+            }
         }
 
-        // Find new value from mappings
-        for range in mappings.get(&current_node.unwrap()).unwrap() {
-            if range.contains(value) {
-                value = range.destination_from_source(value);
-                break;
+        let mut sources = self
+            .ranges
+            .iter()
+            .filter(|range| range.size > 0)
+            .map(|range| (range.source as u128, range.end()))
+            .collect::<Vec<Interval>>();
+        sources.sort();
+        let mut gaps = Vec::<Interval>::new();
+        if let Some(&(_, first_end)) = sources.first() {
+            let mut covered_to = first_end;
+            for (start, end) in sources {
+                if start > covered_to {
+                    gaps.push((covered_to, start));
+                }
+                covered_to = covered_to.max(end);
             }
         }
 
-        let next_node_prefix = current_node.unwrap().split_once("-to-").unwrap().1;
-        return walk_graph(mappings, value, next_node_prefix);
+        MapReport { overlaps, gaps }
     }
+}
 
-    // Part 1
-    let (seeds_str, mappings_str) = include_str!("day5.txt").split_once("\n\n").unwrap();
+/// The maps from one category through to another, in the order values pass through them
+struct Pipeline<'a> {
+    maps: Vec<Map<'a>>,
+}
+
+impl<'a> Pipeline<'a> {
+    /// Chain maps together from `start` to `end`, where every step must have exactly one map.
+    /// Maps off the chain are ignored.
+    fn build(maps: Vec<Map<'a>>, start: &str, end: &str) -> Result<Pipeline<'a>, ChainError> {
+        let mut remaining = maps.into_iter().map(Some).collect::<Vec<Option<Map>>>();
+        let mut chain = Vec::<Map>::new();
+        let mut visited = vec![start.to_string()];
+        while visited.last().unwrap() != end {
+            let category = visited.last().unwrap().clone();
+            let candidates = remaining
+                .iter()
+                .enumerate()
+                .filter(|(_, map)| map.as_ref().is_some_and(|m| m.source == category))
+                .map(|(i, _)| i)
+                .collect::<Vec<usize>>();
+            let map = match candidates[..] {
+                [] => {
+                    return Err(ChainError::MissingStage {
+                        from: category,
+                        to: end.to_string(),
+                    })
+                }
+                [i] => remaining[i].take().unwrap(),
+                _ => {
+                    return Err(ChainError::Ambiguous {
+                        source: category,
+                        maps: candidates
+                            .iter()
+                            .map(|&i| remaining[i].as_ref().unwrap().name())
+                            .collect(),
+                    })
+                }
+            };
+            let looped = visited.iter().any(|v| v == map.destination);
+            visited.push(map.destination.to_string());
+            if looped {
+                return Err(ChainError::Cycle(visited));
+            }
+            chain.push(map);
+        }
+        Ok(Pipeline { maps: chain })
+    }
+
+    /// The part of the chain from `from` to `to`, i.e. `soil-to-fertilizer` ... for `soil` to `humidity`
+    fn stages(&self, from: &str, to: &str) -> Result<&[Map<'a>], ChainError> {
+        let missing = || ChainError::MissingStage {
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+        let first = match self.maps.iter().position(|map| map.source == from) {
+            Some(first) => first,
+            None if self.maps.last().is_some_and(|map| map.destination == from) && from == to => {
+                return Ok(&[])
+            }
+            None => return Err(missing()),
+        };
+        if from == to {
+            return Ok(&[]);
+        }
+        let last = self.maps[first..]
+            .iter()
+            .position(|map| map.destination == to)
+            .ok_or_else(missing)?;
+        Ok(&self.maps[first..=first + last])
+    }
+
+    /// Pass a value through every map in turn, each applying the offset of the range it falls in
+    fn walk(&self, value: u64) -> u64 {
+        self.maps.iter().fold(value, |value, map| map.apply(value))
+    }
+
+    /// Walk whole intervals of values through the maps, as `walk` does for a single value
+    fn walk_intervals(&self, intervals: Vec<Interval>) -> Vec<Interval> {
+        self.maps.iter().fold(intervals, |intervals, map| {
+            map_intervals(&map.ranges, intervals)
+        })
+    }
+
+    /// Compose every map from `from` through to `to`
+    fn compose(&self, from: &str, to: &str) -> Result<Piecewise, ChainError> {
+        Ok(self
+            .stages(from, to)?
+            .iter()
+            .fold(Piecewise::identity(), |composed, map| {
+                composed.then(&Piecewise::from_ranges(&map.ranges))
+            }))
+    }
+
+    /// Which `from` values end up in the `to` intervals, inverting one map at a time from the end
+    fn reverse(
+        &self,
+        from: &str,
+        to: &str,
+        targets: Vec<Interval>,
+    ) -> Result<Vec<Interval>, ChainError> {
+        Ok(self
+            .stages(from, to)?
+            .iter()
+            .rev()
+            .fold(merge_intervals(targets), |targets, map| {
+                Piecewise::from_ranges(&map.ranges).preimage(&targets)
+            }))
+    }
+}
+
+fn parse_almanac(input: &str) -> Result<(Vec<u64>, Pipeline<'_>), ChainError> {
+    let (seeds_str, mappings_str) = input.split_once("\n\n").unwrap();
     let seeds: Vec<u64> = seeds_str
         .split_at(7)
         .1
@@ -72,30 +302,541 @@ fn main() {
         .map(|s| s.parse::<u64>().unwrap())
         .collect();
 
-    let mappings = mappings_str
+    let maps = mappings_str
         .split("\n\n")
-        .map(parse_into_hashmap)
-        .flat_map(|map| map)
-        .collect::<HashMap<&str, Vec<Range>>>();
+        .filter(|block| !block.trim().is_empty())
+        .map(Map::parse)
+        .collect::<Result<Vec<Map>, ChainError>>()?;
 
-    // For each inital seed, walk 'graph' to find location. Take lowest.
-    let closest_location = seeds
-        .iter()
-        .map(|seed| walk_graph(&mappings, *seed, "seed"))
-        .min();
+    Ok((seeds, Pipeline::build(maps, "seed", "location")?))
+}
+
+/// One piece of a piecewise-linear map, sending `start..end` to `start + offset..end + offset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Piece {
+    start: i128,
+    end: i128,
+    offset: i128,
+}
+
+/// A map from every u64 to a u64, as sorted, contiguous pieces (identity where nothing maps)
+#[derive(Debug, PartialEq)]
+struct Piecewise {
+    pieces: Vec<Piece>,
+}
+
+const DOMAIN_END: i128 = 1 << 64;
+
+impl Piecewise {
+    fn identity() -> Piecewise {
+        Piecewise {
+            pieces: vec![Piece {
+                start: 0,
+                end: DOMAIN_END,
+                offset: 0,
+            }],
+        }
+    }
+
+    /// A single map. As in `Map::apply`, where ranges overlap the first one listed wins.
+    fn from_ranges(ranges: &[Range]) -> Piecewise {
+        let mut boundaries = vec![0, DOMAIN_END];
+        for range in ranges {
+            boundaries.push(range.source as i128);
+            boundaries.push(range.end() as i128);
+        }
+        boundaries.sort();
+        boundaries.dedup();
+
+        let pieces = boundaries
+            .windows(2)
+            .map(|w| Piece {
+                start: w[0],
+                end: w[1],
+                offset: ranges
+                    .iter()
+                    .find(|r| r.source as i128 <= w[0] && w[0] < r.end() as i128)
+                    .map_or(0, |r| r.destination as i128 - r.source as i128),
+            })
+            .collect();
+        Piecewise { pieces }.merged()
+    }
+
+    /// Join neighbouring pieces which shift by the same amount
+    fn merged(self) -> Piecewise {
+        let mut pieces = Vec::<Piece>::with_capacity(self.pieces.len());
+        for piece in self.pieces {
+            match pieces.last_mut() {
+                Some(last) if last.offset == piece.offset && last.end == piece.start => {
+                    last.end = piece.end
+                }
+                _ => pieces.push(piece),
+            }
+        }
+        Piecewise { pieces }
+    }
+
+    /// Apply `self` then `next`, splitting each piece wherever its image crosses a piece of `next`
+    fn then(&self, next: &Piecewise) -> Piecewise {
+        let mut pieces = Vec::<Piece>::new();
+        for piece in &self.pieces {
+            let (image_start, image_end) = (piece.start + piece.offset, piece.end + piece.offset);
+            let first = next.pieces.partition_point(|p| p.end <= image_start);
+            for next_piece in next.pieces[first..]
+                .iter()
+                .take_while(|p| p.start < image_end)
+            {
+                pieces.push(Piece {
+                    start: image_start.max(next_piece.start) - piece.offset,
+                    end: image_end.min(next_piece.end) - piece.offset,
+                    offset: piece.offset + next_piece.offset,
+                });
+            }
+        }
+        Piecewise { pieces }.merged()
+    }
+
+    /// O(log n) lookup of a single value
+    fn get(&self, value: u64) -> u64 {
+        let value = value as i128;
+        let piece = &self.pieces[self.pieces.partition_point(|p| p.end <= value)];
+        (value + piece.offset) as u64
+    }
+
+    /// The pieces which move values, written as almanac ranges (`destination source size`)
+    fn table(&self) -> String {
+        self.pieces
+            .iter()
+            .filter(|piece| piece.offset != 0)
+            .map(|piece| {
+                format!(
+                    "{} {} {}",
+                    piece.start + piece.offset,
+                    piece.start,
+                    piece.end - piece.start
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl Piecewise {
+    /// Every value sent into any of the `targets`, as sorted, merged intervals. Values a map
+    /// leaves unmapped are their own preimage.
+    fn preimage(&self, targets: &[Interval]) -> Vec<Interval> {
+        let mut sources = Vec::<Interval>::new();
+        for piece in &self.pieces {
+            for (start, end) in targets {
+                let start = piece.start.max(*start as i128 - piece.offset);
+                let end = piece.end.min(*end as i128 - piece.offset);
+                if start < end {
+                    sources.push((start as u128, end as u128));
+                }
+            }
+        }
+        merge_intervals(sources)
+    }
+}
+
+/// Sort intervals and join any that overlap or touch
+fn merge_intervals(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.sort();
+    let mut merged = Vec::<Interval>::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// `a..b` (half-open), `..b` (below b) or a single value `a`
+fn parse_interval(s: &str) -> Interval {
+    match s.split_once("..") {
+        Some((start, end)) => (
+            if start.is_empty() {
+                0
+            } else {
+                start.parse().unwrap()
+            },
+            end.parse().unwrap(),
+        ),
+        None => {
+            let value = s.parse::<u64>().unwrap() as u128;
+            (value, value + 1)
+        }
+    }
+}
+
+fn show_intervals(intervals: &[Interval]) -> String {
+    match intervals.is_empty() {
+        true => "nothing".to_string(),
+        false => intervals
+            .iter()
+            .map(|(start, end)| format!("{start}..{end}"))
+            .collect::<Vec<String>>()
+            .join(", "),
+    }
+}
+
+fn main() {
+    // Part 1
+    let (seeds, pipeline) =
+        parse_almanac(include_str!("day5.txt")).unwrap_or_else(|e| panic!("{e}"));
+
+    // For each inital seed, walk through the maps to find location. Take lowest.
+    let closest_location = seeds.iter().map(|seed| pipeline.walk(*seed)).min();
 
     println!("{:?}", closest_location);
 
-    // Part 2: We can't brute force for every single seed.. unless we can.
-    let closest_location_seed_ranges = seeds
-        .into_par_iter()
+    // Part 2: We can't brute force for every single seed, so push each seed range through as a
+    // whole, splitting it wherever it straddles the edge of a range. The lowest start wins.
+    let seed_ranges = seeds
         .chunks(2)
-        .flat_map(|range|range[0]..(range[0]+range[1]))
-        .map(|seed| walk_graph(&mappings, seed, "seed"))
+        .map(|range| (range[0] as u128, range[0] as u128 + range[1] as u128))
+        .collect::<Vec<Interval>>();
+    let closest_location_seed_ranges = pipeline
+        .walk_intervals(seed_ranges)
+        .iter()
+        .map(|(start, _)| *start)
         .min();
 
-    println!("{:?}", closest_location_seed_ranges);  // Yes! Rayon + Release build ~1min. 
+    println!("{:?}", closest_location_seed_ranges);
+
+    // `--chain <from>-to-<to>` picks part of the almanac (seed-to-location by default) to squash
+    // into one function, then `--table` prints it and `--lookup <value>` finds where values end up.
+    // `--reverse <a..b|..b|a>` asks the other way round, which values end up in that interval.
+    // `--validate` reports overlapping ranges and gaps in every map.
+    let mut chain = "seed-to-location".to_string();
+    let mut print_table = false;
+    let mut lookups = Vec::<u64>::new();
+    let mut reverse = Vec::<Interval>::new();
+    let mut validate = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--chain" => chain = args.next().expect("--chain needs <from>-to-<to>"),
+            "--table" => print_table = true,
+            "--lookup" => lookups.push(
+                args.next()
+                    .expect("--lookup needs a value")
+                    .parse()
+                    .unwrap(),
+            ),
+            "--reverse" => reverse.push(parse_interval(
+                &args.next().expect("--reverse needs an interval"),
+            )),
+            "--validate" => validate = true,
+            _ => panic!("Unknown argument {arg}"),
+        }
+    }
+
+    if validate {
+        for map in &pipeline.maps {
+            let report = map.validate();
+            println!(
+                "{}: {} ranges, overlapping {}, gaps {}",
+                map.name(),
+                map.ranges.len(),
+                match report.overlaps.is_empty() {
+                    true => "none".to_string(),
+                    false => report
+                        .overlaps
+                        .iter()
+                        .map(|(a, b)| format!("{a} & {b}"))
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                },
+                match report.gaps.is_empty() {
+                    true => "none".to_string(),
+                    false => show_intervals(&report.gaps),
+                }
+            );
+        }
+    }
+
+    if print_table || !lookups.is_empty() || !reverse.is_empty() {
+        let (from, to) = chain
+            .split_once("-to-")
+            .unwrap_or_else(|| panic!("Expected <from>-to-<to>, found {chain}"));
+        let composed = pipeline.compose(from, to).unwrap_or_else(|e| panic!("{e}"));
+        if print_table {
+            println!("{chain} map:\n{}", composed.table());
+        }
+        for value in lookups {
+            println!("{from} {value} -> {to} {}", composed.get(value));
+        }
+        for target in reverse {
+            let sources = pipeline.reverse(from, to, vec![target]).unwrap();
+            println!(
+                "{from} {} -> {to} {}",
+                show_intervals(&sources),
+                show_intervals(&[target])
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        map_intervals, parse_almanac, ChainError, Map, MapReport, Piecewise, Pipeline, Range,
+    };
+
+    const EXAMPLE: &str = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
 
-    // cargo run --release
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
 
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+    #[test]
+    fn test_map_intervals() {
+        let ranges = vec![Range::from_line("50 98 2"), Range::from_line("52 50 48")];
+        let mut mapped = map_intervals(&ranges, vec![(40, 60), (97, 101)]);
+        mapped.sort();
+        assert_eq!(
+            vec![(40, 50), (50, 52), (52, 62), (99, 100), (100, 101)],
+            mapped
+        );
+
+        // A seed range of length 0 has no seeds, so can't be the closest
+        assert_eq!(
+            vec![(52, 62)],
+            map_intervals(&ranges, vec![(1, 1), (50, 60), (99, 99)])
+        );
+    }
+
+    #[test]
+    fn test_example() {
+        let (seeds, pipeline) = parse_almanac(EXAMPLE).unwrap();
+        let locations = seeds
+            .iter()
+            .map(|seed| pipeline.walk(*seed))
+            .collect::<Vec<u64>>();
+        assert_eq!(vec![82, 43, 86, 35], locations);
+
+        let seed_ranges = vec![(79, 79 + 14), (55, 55 + 13)];
+        let intervals = pipeline.walk_intervals(seed_ranges);
+        assert_eq!(Some(46), intervals.iter().map(|(start, _)| *start).min());
+
+        // Every seed is accounted for, one interval at a time
+        assert_eq!(
+            27,
+            intervals
+                .iter()
+                .map(|(start, end)| end - start)
+                .sum::<u128>()
+        );
+    }
+
+    #[test]
+    fn test_compose_chain() {
+        let (_, pipeline) = parse_almanac(EXAMPLE).unwrap();
+        let composed = pipeline.compose("seed", "location").unwrap();
+        // Matches pushing each value through the maps, alone or as an interval
+        for value in 0..200 {
+            assert_eq!(pipeline.walk(value), composed.get(value));
+            assert_eq!(
+                pipeline.walk_intervals(vec![(value as u128, value as u128 + 1)])[0].0,
+                composed.get(value) as u128
+            );
+        }
+        // The top of the range is unmapped, so passes straight through
+        assert_eq!(u64::MAX, composed.get(u64::MAX));
+
+        // Any part of the chain can be composed, and the table reads back as an almanac map
+        let soil_to_humidity = pipeline.compose("soil", "humidity").unwrap();
+        assert_eq!(
+            soil_to_humidity,
+            pipeline
+                .compose("soil", "fertilizer")
+                .unwrap()
+                .then(&pipeline.compose("fertilizer", "humidity").unwrap())
+        );
+        let table = soil_to_humidity
+            .table()
+            .lines()
+            .map(Range::from_line)
+            .collect::<Vec<Range>>();
+        assert_eq!(soil_to_humidity, Piecewise::from_ranges(&table));
+
+        assert!(pipeline.compose("humidity", "soil").is_err());
+        assert_eq!(
+            Piecewise::identity(),
+            pipeline.compose("water", "water").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_reverse_chain() {
+        let (_, pipeline) = parse_almanac(EXAMPLE).unwrap();
+
+        // Seed 13 and nothing else lands on location 35
+        assert_eq!(
+            vec![(13, 14)],
+            pipeline
+                .reverse("seed", "location", vec![(35, 36)])
+                .unwrap()
+        );
+
+        // Both 0..7 and 11..53 land on water 0..49 (via 42..49 and 0..42)
+        let fertilizer = pipeline
+            .reverse("fertilizer", "water", vec![(0, 49)])
+            .unwrap();
+        assert_eq!(vec![(0, 7), (11, 53)], fertilizer);
+
+        // The same as inverting the composed function, and each seed found lands in the target
+        let targets = vec![(40, 50), (60, 61)];
+        let seeds = pipeline
+            .reverse("seed", "location", targets.clone())
+            .unwrap();
+        let composed = pipeline.compose("seed", "location").unwrap();
+        assert_eq!(composed.preimage(&targets), seeds);
+        for seed in 0..200 {
+            let location = composed.get(seed as u64) as u128;
+            let found = seeds
+                .iter()
+                .any(|(start, end)| *start <= seed && seed < *end);
+            let wanted = targets
+                .iter()
+                .any(|(start, end)| *start <= location && location < *end);
+            assert_eq!(wanted, found);
+        }
+    }
+
+    #[test]
+    fn test_half_open_ranges() {
+        let range = Range::from_line("50 98 2");
+        assert!(range.contains(98));
+        assert!(range.contains(99));
+        assert!(!range.contains(100));
+
+        let map = Map::parse("seed-to-soil map:\n50 98 2\n52 50 48").unwrap();
+        assert_eq!(51, map.apply(99));
+        assert_eq!(100, map.apply(100));
+    }
+
+    #[test]
+    fn test_range_ending_at_domain_end() {
+        // The last ten u64s, so the range ends at u64::MAX + 1
+        let map = Map::parse("a-to-b map:\n0 18446744073709551606 10").unwrap();
+        let domain_end = u64::MAX as u128 + 1;
+        assert!(map.ranges[0].contains(u64::MAX));
+        assert_eq!(9, map.apply(u64::MAX));
+        assert_eq!(
+            MapReport {
+                overlaps: vec![],
+                gaps: vec![]
+            },
+            map.validate()
+        );
+
+        let pipeline = Pipeline::build(vec![map], "a", "b").unwrap();
+        assert_eq!(
+            vec![(0, 10)],
+            pipeline.walk_intervals(vec![(domain_end - 10, domain_end)])
+        );
+        // The interval and composed paths agree
+        let composed = pipeline.compose("a", "b").unwrap();
+        assert_eq!(9, composed.get(u64::MAX));
+        assert_eq!(
+            Ok(vec![(0, 10), (domain_end - 10, domain_end)]),
+            pipeline.reverse("a", "b", vec![(0, 10)])
+        );
+    }
+
+    #[test]
+    fn test_chain_errors() {
+        let maps = |blocks: &[&'static str]| {
+            blocks
+                .iter()
+                .map(|block| Map::parse(block).unwrap())
+                .collect::<Vec<Map>>()
+        };
+
+        let missing = maps(&["seed-to-soil map:", "water-to-location map:"]);
+        assert_eq!(
+            Some(ChainError::MissingStage {
+                from: "soil".to_string(),
+                to: "location".to_string()
+            }),
+            Pipeline::build(missing, "seed", "location").err()
+        );
+
+        let ambiguous = maps(&[
+            "seed-to-soil map:",
+            "seed-to-water map:",
+            "seedling-to-location map:",
+        ]);
+        assert_eq!(
+            Some(ChainError::Ambiguous {
+                source: "seed".to_string(),
+                maps: vec!["seed-to-soil".to_string(), "seed-to-water".to_string()]
+            }),
+            Pipeline::build(ambiguous, "seed", "location").err()
+        );
+
+        let cycle = maps(&[
+            "seed-to-soil map:",
+            "soil-to-water map:",
+            "water-to-soil map:",
+        ]);
+        assert_eq!(
+            Some(ChainError::Cycle(
+                ["seed", "soil", "water", "soil"].map(String::from).to_vec()
+            )),
+            Pipeline::build(cycle, "seed", "location").err()
+        );
+
+        // A prefix of the category isn't enough to continue the chain any more
+        let prefix = maps(&["seed-to-soil map:", "soils-to-location map:"]);
+        assert!(Pipeline::build(prefix, "seed", "location").is_err());
+
+        assert_eq!(
+            Some(ChainError::BadHeader("seed to soil".to_string())),
+            Map::parse("seed to soil\n1 2 3").err()
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let map = Map::parse("a-to-b map:\n0 10 5\n0 12 5\n0 20 3\n0 30 1").unwrap();
+        assert_eq!(
+            MapReport {
+                overlaps: vec![(0, 1)],
+                gaps: vec![(17, 20), (23, 30)],
+            },
+            map.validate()
+        );
+    }
 }
@@ -1,50 +1,418 @@
-use std::{iter::zip, ops::Div};
+use std::env;
 
-fn main() {
-    // Part 1
-    fn parse(to_parse: &str) -> Vec<i64> {
-        to_parse
-            .split_once(":")
-            .unwrap()
-            .1
-            .split_ascii_whitespace()
-            .map(|s| s.parse::<i64>().unwrap())
-            .collect::<Vec<i64>>()
+use num::{integer::Roots, BigUint, Integer};
+
+fn parse(to_parse: &str) -> Vec<u128> {
+    to_parse
+        .split_once(":")
+        .unwrap()
+        .1
+        .split_ascii_whitespace()
+        .map(|s| s.parse::<u128>().unwrap())
+        .collect::<Vec<u128>>()
+}
+
+// Part 2 kerns all the numbers together, so parse as an arbitrarily large integer
+fn parse_as_single(to_parse: &str) -> BigUint {
+    to_parse
+        .split_once(":")
+        .unwrap()
+        .1
+        .split_ascii_whitespace()
+        .collect::<Vec<&str>>()
+        .join("")
+        .parse::<BigUint>()
+        .unwrap()
+}
+
+/// Does holding the button for `hold` of the `time` ms go further than `record`
+fn beats<T: Integer + Clone>(time: &T, record: &T, hold: &T) -> bool {
+    hold.clone() * (time.clone() - hold.clone()) > *record
+}
+
+/// Number of whole-ms hold times that beat the record.
+///
+/// Holding for h goes h(time - h), which beats the record between the roots of
+/// h² - time·h + record = 0, i.e. (time ± √(time² - 4·record)) / 2. Taking the integer square
+/// root keeps this exact where `f64` runs out of precision once time² passes 2⁵³, and the
+/// first winning hold is then found by stepping a little either side of the estimate. The
+/// last winning hold mirrors the first around time / 2.
+///
+/// Works for any unsigned integer type, but time² must fit: use `BigUint` beyond about 2⁶⁴.
+fn count_ways<T: Integer + Roots + Clone + From<u8>>(time: &T, record: &T) -> T {
+    let (two, four) = (T::from(2), T::from(4));
+    let square = time.clone() * time.clone();
+    let four_record = four * record.clone();
+    if square <= four_record {
+        return T::zero();
     }
+    let root = (square - four_record).sqrt();
 
-    let (time_str, distance_str) = include_str!("day6.txt").split_once("\n").unwrap();
+    // Rounding down the lower root; correct in either direction until it's the first win
+    let mut first = (time.clone() - root) / two.clone();
+    while !first.is_zero() && beats(time, record, &(first.clone() - T::one())) {
+        first = first - T::one();
+    }
+    while first.clone() * two.clone() <= *time && !beats(time, record, &first) {
+        first = first + T::one();
+    }
+
+    let last = time.clone() - first.clone();
+    match first <= last {
+        true => last - first + T::one(),
+        false => T::zero(),
+    }
+}
+
+/// How the speed builds up while the button is held
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Curve {
+    // Speed grows steadily with the hold time (the puzzle)
+    Linear,
+    // Speed grows with hold time to the power p
+    Power(f64),
+}
+
+impl Curve {
+    fn from_str(s: &str) -> Curve {
+        match s.split_once(':') {
+            None if s == "linear" => Curve::Linear,
+            Some(("power", p)) if p.parse::<f64>().is_ok_and(|p| p > 0.0) => {
+                Curve::Power(p.parse().unwrap())
+            }
+            _ => panic!("Expected linear or power:<p> with p > 0, found {s}"),
+        }
+    }
+}
+
+/// The puzzle is charge rate 1, no top speed, linear and a free button
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Physics {
+    charge_rate: f64,
+    max_speed: Option<f64>,
+    curve: Curve,
+    // Time lost pressing the button, in which the boat neither charges nor moves
+    button_cost: f64,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Physics {
+            charge_rate: 1.0,
+            max_speed: None,
+            curve: Curve::Linear,
+            button_cost: 0.0,
+        }
+    }
+}
+
+/// Everything worth knowing about one race under some physics
+#[derive(Debug, PartialEq)]
+struct Analysis {
+    best_hold: f64,
+    best_distance: f64,
+    // How far past the record the best hold goes (negative if the record can't be beaten)
+    margin: f64,
+    // Hold times strictly between these beat the record
+    winning: Option<(f64, f64)>,
+    // The first and last winning whole-ms holds
+    holds: Option<(u64, u64)>,
+}
+
+impl Physics {
+    fn speed(&self, hold: f64) -> f64 {
+        let speed = self.charge_rate
+            * match self.curve {
+                Curve::Linear => hold,
+                Curve::Power(p) => hold.powf(p),
+            };
+        self.max_speed.map_or(speed, |max| speed.min(max))
+    }
+
+    /// Time left to move after holding for `hold`
+    fn moving(&self, time: f64, hold: f64) -> f64 {
+        (time - self.button_cost - hold).max(0.0)
+    }
+
+    fn distance(&self, time: f64, hold: f64) -> f64 {
+        self.speed(hold) * self.moving(time, hold)
+    }
+
+    /// Hold that reaches top speed, if there is one
+    fn top_speed_hold(&self) -> Option<f64> {
+        let max = self.max_speed?;
+        Some(match self.curve {
+            Curve::Linear => max / self.charge_rate,
+            Curve::Power(p) => (max / self.charge_rate).powf(1.0 / p),
+        })
+    }
+
+    /// Distance rises with hold time up to the best hold and falls after, so setting the
+    /// derivative of speed · moving time to zero gives it directly: half the moving time for
+    /// linear, p / (p + 1) of it for power p. Past top speed holding longer only loses time.
+    fn best_hold(&self, time: f64) -> f64 {
+        let available = (time - self.button_cost).max(0.0);
+        let best = match self.curve {
+            Curve::Linear => available / 2.0,
+            Curve::Power(p) => available * p / (p + 1.0),
+        };
+        self.top_speed_hold().map_or(best, |top| best.min(top))
+    }
 
-    fn compute_quadratic_roots(time: i64, dist: i64) -> i64 {
-        let d = f64::sqrt((time.pow(2) as f64) - (4 * dist) as f64);
-        return (f64::ceil((-time as f64 + d).div(2.0) as f64)
-            - f64::floor((-time as f64 - d).div(2.0) as f64)) as i64
-            - 1;
+    /// Hold times where the distance crosses `record` on either side of the best hold
+    fn crossings(&self, time: f64, record: f64, best: f64) -> (f64, f64) {
+        let available = (time - self.button_cost).max(0.0);
+        match self.curve {
+            // r·h·(T - h) = record before top speed, max·(T - h) = record after it
+            Curve::Linear => {
+                let root = (available * available - 4.0 * record / self.charge_rate).sqrt();
+                let (low, high) = ((available - root) / 2.0, (available + root) / 2.0);
+                match (self.max_speed, self.top_speed_hold()) {
+                    (Some(max), Some(top)) => {
+                        let capped = available - record / max;
+                        (
+                            if low <= top { low } else { capped },
+                            if capped >= top { capped } else { high },
+                        )
+                    }
+                    _ => (low, high),
+                }
+            }
+            Curve::Power(_) => (
+                self.bisect(time, record, 0.0, best),
+                self.bisect(time, record, available, best),
+            ),
+        }
     }
 
-    let part1: i64 = zip(parse(time_str), parse(distance_str))
-        .map(|(time, dist)| compute_quadratic_roots(time, dist))
+    /// Narrow down where the distance crosses `record` between a losing and a winning hold
+    fn bisect(&self, time: f64, record: f64, mut losing: f64, mut winning: f64) -> f64 {
+        for _ in 0..200 {
+            let middle = (losing + winning) / 2.0;
+            match self.distance(time, middle) > record {
+                true => winning = middle,
+                false => losing = middle,
+            }
+        }
+        (losing + winning) / 2.0
+    }
+
+    fn analyse(&self, time: f64, record: f64) -> Analysis {
+        let best_hold = self.best_hold(time);
+        let best_distance = self.distance(time, best_hold);
+        let margin = best_distance - record;
+        if margin <= 0.0 {
+            return Analysis {
+                best_hold,
+                best_distance,
+                margin,
+                winning: None,
+                holds: None,
+            };
+        }
+        let (low, high) = self.crossings(time, record, best_hold);
+
+        // Whole ms just inside the crossings, checked directly in case of rounding
+        let beats = |hold: u64| self.distance(time, hold as f64) > record;
+        let mut first = low.floor().max(0.0) as u64;
+        while first > 0 && beats(first - 1) {
+            first -= 1;
+        }
+        while (first as f64) < high && !beats(first) {
+            first += 1;
+        }
+        let mut last = high.ceil().max(0.0) as u64;
+        while beats(last + 1) {
+            last += 1;
+        }
+        while last > first && !beats(last) {
+            last -= 1;
+        }
+
+        Analysis {
+            best_hold,
+            best_distance,
+            margin,
+            winning: Some((low, high)),
+            holds: Some((first, last)).filter(|_| beats(first)),
+        }
+    }
+}
+
+fn main() {
+    // Part 1
+    let (time_str, distance_str) = include_str!("day6.txt").split_once("\n").unwrap();
+
+    let part1: u128 = parse(time_str)
+        .iter()
+        .zip(parse(distance_str))
+        .map(|(time, dist)| count_ways(time, &dist))
         .product();
 
     println!("{:?}", part1);
 
-    // Part 2  (we needed to swap to 64 bit numbers to parse largest input)
-    fn parse_as_single(to_parse: &str) -> i64 {
-        to_parse
-            .split_once(":")
-            .unwrap()
-            .1
-            .split_ascii_whitespace()
-            .collect::<Vec<&str>>()
-            .join("")
-            .parse::<i64>()
-            .unwrap()
-    }
-
-    let part2: i64 =
-        compute_quadratic_roots(
-            parse_as_single(time_str), 
-            parse_as_single(distance_str),
+    // Part 2
+    let part2 = count_ways(&parse_as_single(time_str), &parse_as_single(distance_str));
+
+    println!("{}", part2);
+
+    // Change the physics with `--charge-rate <r>`, `--max-speed <v>`, `--curve linear|power:<p>`
+    // and `--button-cost <ms>`, then `--analyse` reports the best hold, the margin over the
+    // record and when each race is won.
+    let mut physics = Physics::default();
+    let mut analyse = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("{arg} needs a value"));
+        match arg.as_str() {
+            "--charge-rate" => physics.charge_rate = value().parse().unwrap(),
+            "--max-speed" => physics.max_speed = Some(value().parse().unwrap()),
+            "--curve" => physics.curve = Curve::from_str(&value()),
+            "--button-cost" => physics.button_cost = value().parse().unwrap(),
+            "--analyse" => analyse = true,
+            _ => panic!("Unknown argument {arg}"),
+        }
+    }
+
+    if analyse {
+        let races = parse(time_str).into_iter().zip(parse(distance_str));
+        for (time, record) in races {
+            let analysis = physics.analyse(time as f64, record as f64);
+            print!(
+                "{time} ms, record {record}: best hold {:.3} goes {:.3} (margin {:.3})",
+                analysis.best_hold, analysis.best_distance, analysis.margin
+            );
+            match (analysis.winning, analysis.holds) {
+                (Some((low, high)), Some((first, last))) => println!(
+                    ", wins holding {low:.3}..{high:.3}, {first}..={last} ms ({} ways)",
+                    last - first + 1
+                ),
+                (Some((low, high)), None) => {
+                    println!(", wins holding {low:.3}..{high:.3}, no whole ms")
+                }
+                _ => println!(", can't win"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use crate::{count_ways, Curve, Physics};
+
+    fn brute_force(time: u128, record: u128) -> u128 {
+        (0..=time).filter(|h| h * (time - h) > record).count() as u128
+    }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(4, count_ways(&7_u128, &9));
+        assert_eq!(8, count_ways(&15_u128, &40));
+        assert_eq!(9, count_ways(&30_u128, &200));
+        assert_eq!(71503, count_ways(&71530_u128, &940200));
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        // xorshift, so the cases are random but repeatable
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = |below: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % below
+        };
+        for _ in 0..2000 {
+            let time = next(500) as u128;
+            // Mostly near the best possible distance, where the boundaries are fiddly
+            let best = time * time / 4;
+            let record = match next(2) {
+                0 => best.saturating_sub(next(20) as u128),
+                _ => next(best as u64 + 2) as u128,
+            };
+            assert_eq!(brute_force(time, record), count_ways(&time, &record));
+            assert_eq!(
+                BigUint::from(brute_force(time, record)),
+                count_ways(&BigUint::from(time), &BigUint::from(record))
+            );
+        }
+    }
+
+    #[test]
+    fn test_large() {
+        // Just past where f64 can tell time² from time² - 4
+        let time = 1_u128 << 60;
+        let best = time * time / 4;
+        assert_eq!(1, count_ways(&time, &(best - 1)));
+        assert_eq!(0, count_ways(&time, &best));
+        assert_eq!(3, count_ways(&time, &(best - 2)));
+
+        // Far beyond u128, winning from 10²⁰ up to 10⁴⁰ - 10²⁰
+        let time = BigUint::from(10_u8).pow(40);
+        let first = BigUint::from(10_u8).pow(20);
+        let record = &first * (&time - &first) - 1_u8;
+        assert_eq!(&time - &first * 2_u8 + 1_u8, count_ways(&time, &record));
+        assert_eq!(
+            &time - &first * 2_u8 - 1_u8,
+            count_ways(&time, &(record + 1_u8))
         );
+    }
+
+    #[test]
+    fn test_default_physics() {
+        let physics = Physics::default();
+        for (time, record) in [(7, 9), (15, 40), (30, 200), (4, 4), (3, 2), (10, 0)] {
+            let analysis = physics.analyse(time as f64, record as f64);
+            let ways = analysis.holds.map_or(0, |(first, last)| last - first + 1);
+            assert_eq!(count_ways(&(time as u128), &record) as u64, ways);
+        }
+
+        let analysis = physics.analyse(7.0, 9.0);
+        assert_eq!(
+            (3.5, 12.25, 3.25),
+            (analysis.best_hold, analysis.best_distance, analysis.margin)
+        );
+        assert_eq!(Some((2, 5)), analysis.holds);
+        let (low, high) = analysis.winning.unwrap();
+        assert!((low - (7.0 - 13_f64.sqrt()) / 2.0).abs() < 1e-12);
+        assert!((high - (7.0 + 13_f64.sqrt()) / 2.0).abs() < 1e-12);
+    }
 
-    println!("{:?}", part2);
+    #[test]
+    fn test_custom_physics() {
+        // Top speed 4 after 2 ms, so it's best to stop charging there, and the button takes 1 ms
+        let capped = Physics {
+            charge_rate: 2.0,
+            max_speed: Some(4.0),
+            button_cost: 1.0,
+            ..Physics::default()
+        };
+        let analysis = capped.analyse(11.0, 20.0);
+        assert_eq!(
+            (2.0, 32.0, 12.0),
+            (analysis.best_hold, analysis.best_distance, analysis.margin)
+        );
+        // 2h(10 - h) = 20 at h = 5 - √15 on the way up, 4(10 - h) = 20 at h = 5 on the way down
+        assert_eq!(Some((5.0 - 15_f64.sqrt(), 5.0)), analysis.winning);
+        assert_eq!(Some((2, 4)), analysis.holds);
+
+        // h²(10 - h) is best two thirds of the way along
+        let squared = Physics {
+            curve: Curve::Power(2.0),
+            ..Physics::default()
+        };
+        let analysis = squared.analyse(10.0, 32.0);
+        assert!((analysis.best_hold - 20.0 / 3.0).abs() < 1e-12);
+        // ...and crosses 32 at h = 2 and h = 4 + 4√2
+        let (low, high) = analysis.winning.unwrap();
+        assert!((low - 2.0).abs() < 1e-9);
+        assert!((high - (4.0 + 4.0 * 2_f64.sqrt())).abs() < 1e-9);
+        assert_eq!(Some((3, 9)), analysis.holds);
+        // h = 10 leaves no time to move
+        assert_eq!(Some((1, 9)), squared.analyse(10.0, 0.0).holds);
+
+        assert_eq!(None, squared.analyse(10.0, 200.0).winning);
+    }
 }
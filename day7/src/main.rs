@@ -1,45 +1,137 @@
+mod poker;
+mod probability;
+
 use itertools::Itertools;
-use std::{cmp::Ordering, collections::HashMap};
-
-fn custom_card_order(c: &char) -> u8 {
-    match c {
-        'A' => 1,
-        'K' => 2,
-        'Q' => 3,
-        'J' => 4,
-        'T' => 5,
-        '9' => 6,
-        '8' => 7,
-        '7' => 8,
-        '6' => 9,
-        '5' => 10,
-        '4' => 11,
-        '3' => 12,
-        '2' => 13,
-        _ => panic!("{} isn't a card in camel poker!", c),
+use poker::Poker;
+use probability::{Deck, Rng};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    env, fmt, fs,
+    hash::{Hash, Hasher},
+};
+
+/// A card game that ranks hands, like Camel Cards or poker
+trait Game {
+    /// The first thing compared between hands, which sorts strongest first
+    type HandType: Ord + Copy + fmt::Debug + fmt::Display + Hash;
+
+    fn name(&self) -> &str;
+    /// Panics if `cards` isn't a hand in this game
+    fn hand_type(&self, cards: &[char]) -> Self::HandType;
+    /// Settles hands of the same type, `Less` for the stronger
+    fn tiebreak(&self, a: &[char], b: &[char]) -> Ordering;
+}
+
+/// A variant of Camel Cards: which cards there are, which are wild, and how many make a hand
+#[derive(Debug, Clone, PartialEq)]
+struct Ruleset {
+    name: String,
+    // Every card, strongest first. Ties between hands of the same type go to the stronger card.
+    order: Vec<char>,
+    // Wild cards count as whichever card makes the best hand
+    wild: Vec<char>,
+    hand_size: usize,
+}
+
+impl Ruleset {
+    // Part 1
+    fn standard() -> Ruleset {
+        Ruleset::from_str("standard: AKQJT98765432")
+    }
+
+    // Part 2, where J is a joker, and the weakest card in a tie
+    fn jokers() -> Ruleset {
+        Ruleset::from_str("jokers: AKQT98765432J wild J")
+    }
+
+    /// `<name>: <cards strongest first> [wild <cards>] [size <n>]`, with a hand of 5 by default
+    fn from_str(s: &str) -> Ruleset {
+        let (name, rules) = s
+            .split_once(':')
+            .unwrap_or_else(|| panic!("Expected <name>: <cards>, found {s}"));
+        let mut words = rules.split_ascii_whitespace();
+        let order = words
+            .next()
+            .unwrap_or_else(|| panic!("Ruleset {name} has no cards"))
+            .chars()
+            .collect::<Vec<char>>();
+        let mut ruleset = Ruleset {
+            name: name.trim().to_string(),
+            order,
+            wild: Vec::new(),
+            hand_size: 5,
+        };
+        while let Some(word) = words.next() {
+            let mut value = || {
+                words
+                    .next()
+                    .unwrap_or_else(|| panic!("{word} needs a value in ruleset {name}"))
+            };
+            match word {
+                "wild" => ruleset.wild = value().chars().collect(),
+                "size" => ruleset.hand_size = value().parse().unwrap(),
+                _ => panic!("Unknown rule {word} in ruleset {name}"),
+            }
+        }
+
+        if let Some(c) = ruleset.order.iter().duplicates().next() {
+            panic!("{c} appears twice in ruleset {name}");
+        }
+        if let Some(c) = ruleset.wild.iter().find(|c| !ruleset.order.contains(c)) {
+            panic!("Wild card {c} isn't in ruleset {name}");
+        }
+        if ruleset.hand_size == 0 {
+            panic!("Hands in ruleset {name} need at least one card");
+        }
+        ruleset
+    }
+
+    /// Position of `c` in the card order, so lower is stronger
+    fn strength(&self, c: &char) -> usize {
+        self.order
+            .iter()
+            .position(|card| card == c)
+            .unwrap_or_else(|| panic!("{} isn't a card in {} camel poker!", c, self.name))
     }
 }
 
-fn custom_card_order_joker(c: &char) -> u8 {
-    match c {
-        'A' => 1,
-        'K' => 2,
-        'Q' => 3,
-        'T' => 4,
-        '9' => 5,
-        '8' => 6,
-        '7' => 7,
-        '6' => 8,
-        '5' => 9,
-        '4' => 10,
-        '3' => 11,
-        '2' => 12,
-        'J' => 13,
-        _ => panic!("{} isn't a card in camel poker!", c),
+impl Game for Ruleset {
+    type HandType = HandType;
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn hand_type(&self, cards: &[char]) -> HandType {
+        if cards.len() != self.hand_size {
+            panic!(
+                "{} should have {} cards in {} camel poker",
+                cards.iter().collect::<String>(),
+                self.hand_size,
+                self.name
+            );
+        }
+        if let Some(c) = cards.iter().find(|c| !self.order.contains(c)) {
+            panic!("{} isn't a card in {} camel poker!", c, self.name);
+        }
+        Hand::compute_rank(cards, self)
+    }
+
+    fn tiebreak(&self, a: &[char], b: &[char]) -> Ordering {
+        for (a, b) in a.iter().zip(b).filter(|(a, b)| a != b) {
+            match self.strength(a).cmp(&self.strength(b)) {
+                Ordering::Equal => continue,
+                result => return result,
+            }
+        }
+
+        // If all specified positions are equal, consider the structs equal
+        Ordering::Equal
     }
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
 enum HandType {
     FiveOfAKind,
     FourOfAKind,
@@ -50,223 +142,489 @@ enum HandType {
     HighCard,
 }
 
+impl fmt::Display for HandType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
 #[derive(Debug)]
-struct Hand {
+struct Hand<'g, G: Game = Ruleset> {
     cards: Vec<char>,
-    htype: HandType,
+    htype: G::HandType,
     bid: u32,
-    joker_style: bool,
+    game: &'g G,
 }
 
-impl Ord for Hand {
+impl<G: Game> Ord for Hand<'_, G> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         let primary_cmp = self.htype.cmp(&other.htype);
 
         // If htypes properties are equal, compare based on actual cards
         if primary_cmp == std::cmp::Ordering::Equal {
-            for i in 0..self.cards.len() {
-                if self.joker_style {
-                    match custom_card_order_joker(self.cards.get(i).unwrap())
-                        .cmp(&custom_card_order_joker(&other.cards.get(i).unwrap()))
-                    {
-                        Ordering::Equal => continue,
-                        result => return result,
-                    }
-                } else {
-                    match custom_card_order(self.cards.get(i).unwrap())
-                        .cmp(&custom_card_order(&other.cards.get(i).unwrap()))
-                    {
-                        Ordering::Equal => continue,
-                        result => return result,
-                    }
-                }
-            }
-
-            // If all specified positions are equal, consider the structs equal
-            Ordering::Equal
+            self.game.tiebreak(&self.cards, &other.cards)
         } else {
             primary_cmp
         }
     }
 }
 
-impl PartialOrd for Hand {
+impl<G: Game> PartialOrd for Hand<'_, G> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl PartialEq for Hand {
+// Hands are equal when neither beats the other. The bid doesn't count.
+impl<G: Game> PartialEq for Hand<'_, G> {
     fn eq(&self, other: &Self) -> bool {
-        self.htype == other.htype
+        self.cmp(other) == Ordering::Equal
     }
 }
 
-impl Eq for Hand {}
+impl<G: Game> Eq for Hand<'_, G> {}
 
-impl Hand {
-    fn new(cards: &str, bid: u32) -> Hand {
-        let cards_vec = cards.chars().collect::<Vec<char>>();
-        Hand {
-            cards: cards_vec.clone(),
-            htype: Hand::compute_rank(cards_vec),
-            bid: bid,
-            joker_style: false,
-        }
+impl<G: Game> Hash for Hand<'_, G> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.htype.hash(state);
     }
+}
 
-    fn new_joker_style(cards: &str, bid: u32) -> Hand {
+impl<'g, G: Game> Hand<'g, G> {
+    fn new(cards: &str, bid: u32, game: &'g G) -> Hand<'g, G> {
         let cards_vec = cards.chars().collect::<Vec<char>>();
         Hand {
-            cards: cards_vec.clone(),
-            htype: Hand::compute_rank_joker_style(cards_vec),
-            bid: bid,
-            joker_style: true,
+            htype: game.hand_type(&cards_vec),
+            cards: cards_vec,
+            bid,
+            game,
         }
     }
 
-    fn default(cards: &str) -> Hand {
-        Hand::new(cards, 0)
-    }
-
-    fn from_line(line: &str) -> Hand {
+    fn from_line(line: &str, game: &'g G) -> Hand<'g, G> {
         let (cards, bid_str) = line.split_once(" ").unwrap();
-        Hand::new(cards, bid_str.parse::<u32>().unwrap())
+        Hand::new(cards, bid_str.parse::<u32>().unwrap(), game)
     }
+}
 
-    fn from_line_joker_style(line: &str) -> Hand {
-        let (cards, bid_str) = line.split_once(" ").unwrap();
-        Hand::new_joker_style(cards, bid_str.parse::<u32>().unwrap())
+impl Hand<'_> {
+    #[cfg(test)]
+    fn default(cards: &str) -> Hand<'static> {
+        static STANDARD: std::sync::LazyLock<Ruleset> = std::sync::LazyLock::new(Ruleset::standard);
+        Hand::new(cards, 0, &*STANDARD)
     }
 
-    fn compute_rank(cards: Vec<char>) -> HandType {
+    /// Hands are typed by their two largest groups of matching cards, so bigger hands than 5 only
+    /// count the best of what they hold, e.g. 4 and 3 of a kind is still FourOfAKind. Wild cards
+    /// always do best joining the largest group of the others.
+    fn compute_rank(cards: &[char], ruleset: &Ruleset) -> HandType {
         // Count each card in the hand
-        let mut counts = HashMap::<char, i32>::new();
-        for c in cards.clone() {
-            *counts.entry(c).or_insert(0) += 1;
+        let mut counts = HashMap::<char, usize>::new();
+        for c in cards {
+            *counts.entry(*c).or_insert(0) += 1;
         }
 
-        // Exhaustively check possible combinations
-        let mut count_values = counts.values().collect::<Vec<&i32>>();
-        // Iterate in reverse sorted order, to force largest card counts to appear first
+        // Specifically check the number of wild cards (they act as anything!)
+        let wild_count = ruleset
+            .wild
+            .iter()
+            .filter_map(|c| counts.remove(c))
+            .sum::<usize>();
+
+        // Largest card counts first
+        let mut count_values = counts.into_values().collect::<Vec<usize>>();
         count_values.sort();
         count_values.reverse();
 
-        for card_count in count_values {
-            match card_count {
-                5 => return HandType::FiveOfAKind,
-                4 => return HandType::FourOfAKind,
-                3 => {
-                    // Full house & 3 of a kind check
-                    if counts.len() == 2 {
-                        return HandType::FullHouse;
-                    } else {
-                        return HandType::ThreeOfAKind;
-                    }
-                }
-                1 => {
-                    if counts.len() == 3 {
-                        return HandType::TwoPair;
-                    } else if counts.len() == 4 {
-                        return HandType::OnePair;
-                    } else {
-                        return HandType::HighCard;
-                    }
-                }
-                _ => continue,
-            };
+        let largest = count_values.first().unwrap_or(&0) + wild_count;
+        let second = *count_values.get(1).unwrap_or(&0);
+        match (largest, second) {
+            (5.., _) => HandType::FiveOfAKind,
+            (4, _) => HandType::FourOfAKind,
+            (3, 2..) => HandType::FullHouse,
+            (3, _) => HandType::ThreeOfAKind,
+            (2, 2) => HandType::TwoPair,
+            (2, _) => HandType::OnePair,
+            _ => HandType::HighCard,
         }
-        panic!("Failed to find the rank for {:?}!", cards);
     }
+}
 
-    // (part 2) Now cards can be jokers
-    fn compute_rank_joker_style(cards: Vec<char>) -> HandType {
-        // Count each card in the hand
-        let mut counts = HashMap::<char, i32>::new();
-        for c in cards.clone() {
-            *counts.entry(c).or_insert(0) += 1;
+/// What the wild cards in a hand should become
+#[derive(Debug, PartialEq)]
+struct Explanation {
+    original: Vec<char>,
+    best: Vec<char>,
+    htype: HandType,
+    // Every other type the wild cards could make, strongest first
+    alternatives: Vec<HandType>,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} → {} ({:?})",
+            self.original.iter().collect::<String>(),
+            self.best.iter().collect::<String>(),
+            self.htype
+        )?;
+        if !self.alternatives.is_empty() {
+            write!(
+                f,
+                ", could also be {}",
+                self.alternatives
+                    .iter()
+                    .map(|t| format!("{t:?}"))
+                    .join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Explanation {
+    /// Try every way of swapping the wild cards for real ones. The best type goes to the
+    /// strongest cards that reach it.
+    fn new(cards: &[char], ruleset: &Ruleset) -> Explanation {
+        let wild_positions = (0..cards.len())
+            .filter(|i| ruleset.wild.contains(&cards[*i]))
+            .collect::<Vec<usize>>();
+        let targets = ruleset
+            .order
+            .iter()
+            .filter(|c| !ruleset.wild.contains(c))
+            .collect::<Vec<&char>>();
+
+        let mut reachable = Vec::<(HandType, Vec<char>)>::new();
+        for substitution in targets
+            .iter()
+            .copied()
+            .combinations_with_replacement(wild_positions.len())
+        {
+            let mut swapped = cards.to_vec();
+            for (i, c) in wild_positions.iter().zip(substitution) {
+                swapped[*i] = *c;
+            }
+            let htype = Hand::compute_rank(&swapped, ruleset);
+            if !reachable.iter().any(|(t, _)| *t == htype) {
+                reachable.push((htype, swapped));
+            }
         }
+        // With nothing to swap for (every card is wild), the cards can only stand for themselves
+        if reachable.is_empty() {
+            reachable.push((Hand::compute_rank(cards, ruleset), cards.to_vec()));
+        }
+        reachable.sort();
+
+        let (htype, best) = reachable.remove(0);
+        Explanation {
+            original: cards.to_vec(),
+            best,
+            htype,
+            alternatives: reachable.into_iter().map(|(t, _)| t).collect(),
+        }
+    }
+}
 
-        // Specifically check the number of jokers (they act as wildcards!)
-        let joker_count = &counts.get(&'J').unwrap_or(&0).clone();
+/// One line of the ranking report
+#[derive(Debug)]
+struct Ranked<'g, G: Game = Ruleset> {
+    // From 1 for the weakest hand
+    rank: usize,
+    hand: Hand<'g, G>,
+    winnings: u64,
+    // Hands that tie (e.g. the same cards) share a group, numbered from 1. Their ranks are in
+    // input order, but could be swapped without changing which hand is stronger.
+    tie_group: Option<usize>,
+}
 
-        if joker_count == &5 {
-            return HandType::FiveOfAKind; // 5 Jokers!
+/// Every hand in rank order, weakest first
+fn ranking<'g, G: Game>(input: &str, game: &'g G) -> Vec<Ranked<'g, G>> {
+    let hands = input
+        .lines()
+        .map(|line| Hand::from_line(line, game))
+        .sorted_by(|a, b| b.cmp(a))
+        .collect::<Vec<Hand<G>>>();
+
+    let mut tie_groups = HashMap::<&Hand<G>, usize>::new();
+    for (a, b) in hands.iter().tuple_windows() {
+        if a == b && !tie_groups.contains_key(a) {
+            tie_groups.insert(a, tie_groups.len() + 1);
         }
+    }
+    let tie_groups = hands
+        .iter()
+        .map(|hand| tie_groups.get(hand).copied())
+        .collect::<Vec<Option<usize>>>();
+
+    hands
+        .into_iter()
+        .zip(tie_groups)
+        .enumerate()
+        .map(|(i, (hand, tie_group))| Ranked {
+            rank: i + 1,
+            winnings: u64::from(hand.bid) * (i + 1) as u64,
+            hand,
+            tie_group,
+        })
+        .collect()
+}
 
-        counts.remove(&'J');
+/// Total of each bid times the hand's rank, from 1 for the weakest hand
+fn winnings<G: Game>(input: &str, game: &G) -> u64 {
+    ranking(input, game).iter().map(|r| r.winnings).sum()
+}
 
-        // Exhaustively check possible combinations
-        let mut count_values = counts.values().collect::<Vec<&i32>>();
-        // Iterate in reverse sorted order, to force largest card counts to appear first
-        count_values.sort();
-        count_values.reverse();
+// Rows from different games can go in the same report
+fn to_csv(rows: impl IntoIterator<Item = String>) -> String {
+    let mut csv = "ruleset,rank,cards,type,bid,winnings,tie_group\n".to_string();
+    for row in rows {
+        csv += &row;
+    }
+    csv
+}
 
-        for card_count in count_values {
-            match card_count + joker_count {
-                5 => return HandType::FiveOfAKind,
-                4 => return HandType::FourOfAKind,
-                3 => {
-                    // Full house & 3 of a kind check
-                    if counts.len() == 2 {
-                        return HandType::FullHouse;
-                    } else {
-                        return HandType::ThreeOfAKind;
-                    }
-                }
-                2 => {
-                    if joker_count == &0 {
-                        if counts.len() == 3 {
-                            return HandType::TwoPair;
-                        } else if counts.len() == 4 {
-                            return HandType::OnePair;
-                        }
-                    } else {
-                        return HandType::OnePair;
-                    }
-                }
-                1 => {
-                    return HandType::HighCard;
-                }
-                _ => continue,
-            };
+fn csv_row<G: Game>(r: &Ranked<G>) -> String {
+    format!(
+        "{},{},{},{},{},{},{}\n",
+        csv_field(r.hand.game.name()),
+        r.rank,
+        csv_field(&r.hand.cards.iter().collect::<String>()),
+        r.hand.htype,
+        r.hand.bid,
+        r.winnings,
+        r.tie_group.map_or(String::new(), |g| g.to_string())
+    )
+}
+
+fn csv_field(s: &str) -> String {
+    match s.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.to_string(),
+    }
+}
+
+fn to_json(rows: impl IntoIterator<Item = String>) -> String {
+    format!("[\n{}\n]\n", rows.into_iter().join(",\n"))
+}
+
+fn json_row<G: Game>(r: &Ranked<G>) -> String {
+    format!(
+        "  {{\"ruleset\": {}, \"rank\": {}, \"cards\": {}, \"type\": {}, \"bid\": {}, \"winnings\": {}, \"tie_group\": {}}}",
+        json_string(r.hand.game.name()),
+        r.rank,
+        json_string(&r.hand.cards.iter().collect::<String>()),
+        json_string(&r.hand.htype.to_string()),
+        r.hand.bid,
+        r.winnings,
+        r.tie_group.map_or("null".to_string(), |g| g.to_string())
+    )
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = "\"".to_string();
+    for c in s.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            c if c.is_control() => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
         }
-        panic!("Failed to find the rank for {:?}!", cards);
     }
+    json + "\""
 }
 
 fn main() {
     // Camel Poker
+    let input = include_str!("day7.txt");
 
     // Part 1
-    let winnings_part_1: u32 = include_str!("day7.txt")
-        .lines()
-        .map(Hand::from_line)
-        .sorted()
-        .rev()
-        .enumerate()
-        .map(|(rank, hand)| hand.bid * (rank + 1) as u32)
-        .sum();
-
-    println!("{:?}", winnings_part_1);
+    println!("{:?}", winnings(input, &Ruleset::standard()));
 
     // Part 2
-    let winnings_part_2: u32 = include_str!("day7.txt")
-        .lines()
-        .map(Hand::from_line_joker_style)
-        .sorted()
-        .rev()
-        .enumerate()
-        .map(|(rank, hand)| hand.bid * (rank + 1) as u32)
-        .sum();
+    println!("{:?}", winnings(input, &Ruleset::jokers()));
+
+    // More variants come from `--ruleset "<name>: <cards> [wild <cards>] [size <n>]"` and/or
+    // `--rules <file>` (one ruleset per line, `#` comments). `--explain` shows what the wild
+    // cards in each hand become, under part 2 and any other ruleset with wild cards.
+    // `--poker <file>` ranks real poker hands (like `AsKsQsJsTs 100`) from another file.
+    // `--csv <path>` and `--json <path>` write the full ranking under every ruleset.
+    // `--odds` gives the chance of each hand type and `--versus <a> <b>` the chance of partial
+    // hand a (like `KKJ`, or `K?J` with the rest to come) beating b, under every ruleset. These
+    // deal from 4 of each card unless given `--deck <every card>`, and are exact unless asked
+    // for `--samples <n>` random deals (from `--seed <n>`).
+    let mut rulesets = Vec::<Ruleset>::new();
+    let mut poker_input = None;
+    let mut odds = false;
+    let mut versus = Vec::<(String, String)>::new();
+    let mut deck_cards = None;
+    let mut samples = None;
+    let mut seed = 1;
+    let mut explain = false;
+    let mut csv_path = None;
+    let mut json_path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ruleset" => rulesets.push(Ruleset::from_str(
+                &args.next().expect("--ruleset needs a value"),
+            )),
+            "--rules" => {
+                let path = args.next().expect("--rules needs a path");
+                let config = fs::read_to_string(&path)
+                    .unwrap_or_else(|e| panic!("Couldn't read rules {path}: {e}"));
+                rulesets.extend(
+                    config
+                        .lines()
+                        .map(|line| line.split('#').next().unwrap().trim())
+                        .filter(|line| !line.is_empty())
+                        .map(Ruleset::from_str),
+                );
+            }
+            "--explain" => explain = true,
+            "--poker" => {
+                let path = args.next().expect("--poker needs a path");
+                poker_input = Some(
+                    fs::read_to_string(&path)
+                        .unwrap_or_else(|e| panic!("Couldn't read hands {path}: {e}")),
+                );
+            }
+            "--odds" => odds = true,
+            "--versus" => versus.push((
+                args.next().expect("--versus needs two hands"),
+                args.next().expect("--versus needs two hands"),
+            )),
+            "--deck" => deck_cards = Some(args.next().expect("--deck needs cards")),
+            "--samples" => {
+                samples = Some(
+                    args.next()
+                        .expect("--samples needs a number")
+                        .parse::<u64>()
+                        .expect("--samples should be a number"),
+                )
+            }
+            "--seed" => {
+                seed = args
+                    .next()
+                    .expect("--seed needs a number")
+                    .parse::<u64>()
+                    .expect("--seed should be a number")
+            }
+            "--csv" => csv_path = Some(args.next().expect("--csv needs a path")),
+            "--json" => json_path = Some(args.next().expect("--json needs a path")),
+            _ => panic!("Unknown argument {arg}"),
+        }
+    }
+
+    for ruleset in &rulesets {
+        println!("{}: {}", ruleset.name, winnings(input, ruleset));
+    }
+    let poker_ranked = poker_input
+        .as_ref()
+        .map(|hands| ranking(hands, &Poker))
+        .unwrap_or_default();
+    if poker_input.is_some() {
+        println!(
+            "{}: {}",
+            Poker.name(),
+            poker_ranked.iter().map(|r| r.winnings).sum::<u64>()
+        );
+    }
 
-    println!("{:?}", winnings_part_2);
+    if explain {
+        let jokers = Ruleset::jokers();
+        for ruleset in [&jokers].into_iter().chain(&rulesets) {
+            if ruleset.wild.is_empty() {
+                continue;
+            }
+            println!("{}:", ruleset.name);
+            for line in input.lines() {
+                let hand = Hand::from_line(line, ruleset);
+                println!("{}", Explanation::new(&hand.cards, ruleset));
+            }
+        }
+    }
+
+    if odds || !versus.is_empty() {
+        let (standard, jokers) = (Ruleset::standard(), Ruleset::jokers());
+        let mut rng = Rng::new(seed);
+        for ruleset in [&standard, &jokers].into_iter().chain(&rulesets) {
+            let deck = match &deck_cards {
+                Some(cards) => Deck::from_cards(cards, ruleset).unwrap_or_else(|e| panic!("{e}")),
+                None => Deck::new(ruleset, 4),
+            };
+            if odds {
+                let table = match samples {
+                    Some(n) => probability::sampled_odds(&deck, ruleset, n, &mut rng),
+                    None => probability::exact_odds(&deck, ruleset),
+                };
+                match table {
+                    Ok(table) => {
+                        println!("{} odds from {} hands:", ruleset.name, table.total);
+                        for (htype, count) in &table.counts {
+                            println!(
+                                "  {htype:?}: {count} ({:.4}%)",
+                                100.0 * table.probability(*htype)
+                            );
+                        }
+                    }
+                    Err(e) => println!("{}: {e}", ruleset.name),
+                }
+            }
+            for (a, b) in &versus {
+                let matchup = match samples {
+                    Some(n) => probability::sampled_matchup(&deck, ruleset, a, b, n, &mut rng),
+                    None => probability::exact_matchup(&deck, ruleset, a, b),
+                };
+                match matchup {
+                    Ok(m) => println!(
+                        "{} {a} vs {b}: win {:.4}%, tie {:.4}%, lose {:.4}%",
+                        ruleset.name,
+                        100.0 * m.win,
+                        100.0 * m.tie,
+                        100.0 * m.lose
+                    ),
+                    Err(e) => println!("{} {a} vs {b}: {e}", ruleset.name),
+                }
+            }
+        }
+    }
+
+    if csv_path.is_some() || json_path.is_some() {
+        let (standard, jokers) = (Ruleset::standard(), Ruleset::jokers());
+        let ranked = [&standard, &jokers]
+            .into_iter()
+            .chain(&rulesets)
+            .flat_map(|ruleset| ranking(input, ruleset))
+            .collect::<Vec<Ranked>>();
+        let csv = to_csv(
+            ranked
+                .iter()
+                .map(csv_row)
+                .chain(poker_ranked.iter().map(csv_row)),
+        );
+        let json = to_json(
+            ranked
+                .iter()
+                .map(json_row)
+                .chain(poker_ranked.iter().map(json_row)),
+        );
+        for (path, report) in [(csv_path, csv), (json_path, json)] {
+            if let Some(path) = path {
+                fs::write(&path, report).unwrap_or_else(|e| panic!("Couldn't write {path}: {e}"));
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
+
     use crate::Hand;
     use crate::HandType;
+    use crate::{csv_row, json_row, ranking, to_csv, to_json, winnings, Explanation, Ruleset};
 
     #[test]
     fn test_hand_type_ordering() {
@@ -300,25 +658,25 @@ mod tests {
     #[test]
     fn test_hand_type_ordering_and_eq() {
         let mut shuffled_hands = vec![
-            Hand::default("AAAAB"),
-            Hand::default("AABCD"),
+            Hand::default("AAAAK"),
+            Hand::default("AAKQJ"),
             Hand::default("22222"),
             Hand::default("AAAAA"),
-            Hand::default("ABCDE"),
-            Hand::default("AAABC"),
-            Hand::default("AABBC"),
-            Hand::default("AAABB"),
+            Hand::default("AKQJT"),
+            Hand::default("AAAKQ"),
+            Hand::default("AAKKQ"),
+            Hand::default("AAAKK"),
         ];
 
         let expected_order = vec![
             Hand::default("AAAAA"), // Note, AAAAA and 22222 have same type,
-            Hand::default("22222"), // so ordering is based on our custom_card_order func!
-            Hand::default("AAAAB"),
-            Hand::default("AAABB"),
-            Hand::default("AAABC"),
-            Hand::default("AABBC"),
-            Hand::default("AABCD"),
-            Hand::default("ABCDE"),
+            Hand::default("22222"), // so ordering is based on the ruleset's card order!
+            Hand::default("AAAAK"),
+            Hand::default("AAAKK"),
+            Hand::default("AAAKQ"),
+            Hand::default("AAKKQ"),
+            Hand::default("AAKQJ"),
+            Hand::default("AKQJT"),
         ];
 
         assert_ne!(expected_order, shuffled_hands);
@@ -327,4 +685,141 @@ mod tests {
 
         assert_eq!(expected_order, shuffled_hands);
     }
+
+    #[test]
+    #[should_panic(expected = "Z isn't a card in standard camel poker!")]
+    fn test_hand_with_unknown_card() {
+        // Identical hands never reach a tiebreak, so this has to be caught up front
+        Hand::new("AAAAZ", 0, &Ruleset::standard());
+    }
+
+    #[test]
+    fn test_rulesets() {
+        let example = "32T3K 765\nT55J5 684\nKK677 28\nKTJJT 220\nQQQJA 483";
+        assert_eq!(6440, winnings(example, &Ruleset::standard()));
+        assert_eq!(5905, winnings(example, &Ruleset::jokers()));
+
+        let jokers = Ruleset::jokers();
+        let rank = |cards: &str| Hand::compute_rank(&cards.chars().collect::<Vec<char>>(), &jokers);
+        assert_eq!(HandType::FiveOfAKind, rank("JJJJJ"));
+        assert_eq!(HandType::FourOfAKind, rank("KTJJT"));
+        assert_eq!(HandType::FullHouse, rank("KKJQQ"));
+        assert_eq!(HandType::OnePair, rank("2345J"));
+
+        // Three card hands where 2 and 3 are both wild, and the weakest
+        let tiny = Ruleset::from_str("tiny: AKQ32 wild 32 size 3");
+        assert_eq!(vec!['3', '2'], tiny.wild);
+        let rank = |cards: &str| Hand::compute_rank(&cards.chars().collect::<Vec<char>>(), &tiny);
+        assert_eq!(HandType::ThreeOfAKind, rank("A32"));
+        assert_eq!(HandType::OnePair, rank("AK3"));
+        assert_eq!(HandType::HighCard, rank("AKQ"));
+        // AKQ ranks 1, AKA 2 and Q22 (three queens) 3
+        assert_eq!(1 + 3 * 2 + 2 * 3, winnings("AKQ 1\nAKA 3\nQ22 2", &tiny));
+
+        // Seven card hands keep their two biggest groups
+        let seven = Ruleset::from_str("seven: AKQJT98765432 size 7");
+        let rank = |cards: &str| Hand::compute_rank(&cards.chars().collect::<Vec<char>>(), &seven);
+        assert_eq!(HandType::FourOfAKind, rank("AAAAKKK"));
+        assert_eq!(HandType::FullHouse, rank("AAAKKQQ"));
+        assert_eq!(HandType::TwoPair, rank("AAKKQQJ"));
+    }
+
+    #[test]
+    fn test_explanation() {
+        let jokers = Ruleset::jokers();
+        let explain =
+            |cards: &str| Explanation::new(&cards.chars().collect::<Vec<char>>(), &jokers);
+
+        let explanation = explain("KTJJT");
+        assert_eq!("KTTTT".chars().collect::<Vec<char>>(), explanation.best);
+        assert_eq!(
+            vec![
+                HandType::FullHouse,
+                HandType::ThreeOfAKind,
+                HandType::TwoPair,
+                HandType::OnePair
+            ],
+            explanation.alternatives
+        );
+        assert_eq!(
+            "KTJJT → KTTTT (FourOfAKind), could also be FullHouse, ThreeOfAKind, TwoPair, OnePair",
+            explanation.to_string()
+        );
+
+        assert_eq!("JJJJJ → AAAAA (FiveOfAKind), could also be FourOfAKind, FullHouse, ThreeOfAKind, TwoPair, OnePair, HighCard".to_string(), explain("JJJJJ").to_string());
+        assert_eq!(
+            "2345J → 23455 (OnePair), could also be HighCard",
+            explain("2345J").to_string()
+        );
+        assert_eq!("32T3K → 32T3K (OnePair)", explain("32T3K").to_string());
+
+        // The explained type always agrees with the rank, here for every hand of A, K, 2 and J
+        for cards in std::iter::repeat_n("AK2J".chars(), 5).multi_cartesian_product() {
+            assert_eq!(
+                Hand::compute_rank(&cards, &jokers),
+                Explanation::new(&cards, &jokers).htype
+            );
+        }
+    }
+
+    #[test]
+    fn test_hand_eq() {
+        // Same type, different cards
+        assert_ne!(Hand::default("KK677"), Hand::default("KTJJT"));
+        // Stronger hands sort first
+        assert!(Hand::default("KK677") < Hand::default("KTJJT"));
+        // Same cards, different bids
+        let standard = Ruleset::standard();
+        let (a, b) = (
+            Hand::new("KK677", 1, &standard),
+            Hand::new("KK677", 2, &standard),
+        );
+        assert_eq!(a, b);
+        assert_eq!(std::cmp::Ordering::Equal, a.cmp(&b));
+        assert_eq!(
+            1,
+            [a, b]
+                .into_iter()
+                .collect::<std::collections::HashSet<Hand>>()
+                .len()
+        );
+    }
+
+    #[test]
+    fn test_ranking_report() {
+        let standard = Ruleset::standard();
+        let ranked = ranking("KK677 28\n32T3K 765\nKK677 5\nT55J5 684", &standard);
+        let rows = ranked
+            .iter()
+            .map(|r| {
+                (
+                    r.rank,
+                    r.hand.cards.iter().collect::<String>(),
+                    r.winnings,
+                    r.tie_group,
+                )
+            })
+            .collect::<Vec<(usize, String, u64, Option<usize>)>>();
+        // The two KK677s tie, so keep their input order
+        assert_eq!(
+            vec![
+                (1, "32T3K".to_string(), 765, None),
+                (2, "KK677".to_string(), 28 * 2, Some(1)),
+                (3, "KK677".to_string(), 5 * 3, Some(1)),
+                (4, "T55J5".to_string(), 684 * 4, None)
+            ],
+            rows
+        );
+
+        let csv = to_csv(ranked[..2].iter().map(csv_row));
+        assert_eq!(
+            "ruleset,rank,cards,type,bid,winnings,tie_group\nstandard,1,32T3K,OnePair,765,765,\nstandard,2,KK677,TwoPair,28,56,1\n",
+            csv
+        );
+        let json = to_json(ranked[..1].iter().map(json_row));
+        assert_eq!(
+            "[\n  {\"ruleset\": \"standard\", \"rank\": 1, \"cards\": \"32T3K\", \"type\": \"OnePair\", \"bid\": 765, \"winnings\": 765, \"tie_group\": null}\n]\n",
+            json
+        );
+    }
 }
@@ -0,0 +1,211 @@
+// Standard five card poker, ranked through the same `Hand` as Camel Cards.
+//
+// Each card is a rank (2-9, T, J, Q, K, A) followed by a suit (c, d, h or s), all written
+// together like `AsKsQsJsTs 100`. Hands go by category first, then by the ranks that matter
+// within it: the groups from largest to smallest, then the highest card of each. Suits never
+// break ties, and an ace can play low in the straight A2345 (a five-high straight).
+//
+// Everything that separates two hands is packed into a `PokerHand` once, so comparing hands is
+// a couple of integer comparisons and ranking millions of them is dominated by the sort.
+
+use std::{cmp::Ordering, cmp::Reverse, fmt};
+
+use crate::Game;
+
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Category {
+    StraightFlush,
+    FourOfAKind,
+    FullHouse,
+    Flush,
+    Straight,
+    ThreeOfAKind,
+    TwoPair,
+    OnePair,
+    HighCard,
+}
+
+/// Sorts strongest first, like `HandType`
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct PokerHand {
+    pub category: Category,
+    // The ranks that decide ties within the category, 4 bits each, most important highest
+    kickers: Reverse<u32>,
+}
+
+impl fmt::Display for PokerHand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.category)
+    }
+}
+
+#[derive(Debug)]
+pub struct Poker;
+
+/// 2 up to 14 for an ace
+fn rank(c: char) -> u32 {
+    match c {
+        '2'..='9' => c as u32 - '0' as u32,
+        'T' => 10,
+        'J' => 11,
+        'Q' => 12,
+        'K' => 13,
+        'A' => 14,
+        _ => panic!("{c} isn't a rank in poker!"),
+    }
+}
+
+fn suit(c: char) -> u32 {
+    match c {
+        'c' => 0,
+        'd' => 1,
+        'h' => 2,
+        's' => 3,
+        _ => panic!("{c} isn't a suit in poker!"),
+    }
+}
+
+pub fn evaluate(cards: &[char]) -> PokerHand {
+    if cards.len() != 10 {
+        panic!(
+            "{} should be 5 cards of rank then suit",
+            cards.iter().collect::<String>()
+        );
+    }
+
+    let mut counts = [0_u8; 15];
+    // A bit for each rank present, and for each card dealt
+    let mut seen = 0_u32;
+    let mut dealt = 0_u64;
+    let first_suit = suit(cards[1]);
+    let mut flush = true;
+    for card in cards.chunks(2) {
+        let (r, s) = (rank(card[0]), suit(card[1]));
+        if dealt & 1 << (r * 4 + s) != 0 {
+            panic!("{}{} appears twice in one hand", card[0], card[1]);
+        }
+        dealt |= 1 << (r * 4 + s);
+        counts[r as usize] += 1;
+        seen |= 1 << r;
+        flush &= s == first_suit;
+    }
+
+    // Five different ranks in a row, where A2345 counts as five high
+    let straight_top = match seen.count_ones() {
+        5 if seen == (1 << 14) | 0b11_1100 => Some(5),
+        5 if seen >> seen.trailing_zeros() == 0b1_1111 => Some(seen.trailing_zeros() + 4),
+        _ => None,
+    };
+
+    // Ranks by how many of them there are, then how high they are
+    let mut kickers = 0_u32;
+    let mut groups = [0_u8; 2];
+    let mut g = 0;
+    for count in (1..=4).rev() {
+        for r in (2..=14).rev() {
+            if counts[r] == count {
+                kickers = kickers << 4 | r as u32;
+                if g < 2 {
+                    groups[g] = count;
+                    g += 1;
+                }
+            }
+        }
+    }
+
+    let category = match (straight_top, flush, groups) {
+        (Some(_), true, _) => Category::StraightFlush,
+        (_, _, [4, _]) => Category::FourOfAKind,
+        (_, _, [3, 2]) => Category::FullHouse,
+        (_, true, _) => Category::Flush,
+        (Some(_), _, _) => Category::Straight,
+        (_, _, [3, _]) => Category::ThreeOfAKind,
+        (_, _, [2, 2]) => Category::TwoPair,
+        (_, _, [2, _]) => Category::OnePair,
+        _ => Category::HighCard,
+    };
+
+    PokerHand {
+        category,
+        kickers: Reverse(straight_top.unwrap_or(kickers)),
+    }
+}
+
+impl Game for Poker {
+    type HandType = PokerHand;
+
+    fn name(&self) -> &str {
+        "poker"
+    }
+
+    fn hand_type(&self, cards: &[char]) -> PokerHand {
+        evaluate(cards)
+    }
+
+    // Everything that matters is already in the `PokerHand`
+    fn tiebreak(&self, _: &[char], _: &[char]) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::poker::{evaluate, Category, Poker};
+    use crate::{winnings, Hand};
+
+    fn category(cards: &str) -> Category {
+        evaluate(&cards.chars().collect::<Vec<char>>()).category
+    }
+
+    #[test]
+    fn test_categories() {
+        assert_eq!(Category::StraightFlush, category("AsKsQsJsTs"));
+        assert_eq!(Category::StraightFlush, category("As2s3s4s5s"));
+        assert_eq!(Category::FourOfAKind, category("9c9d9h9sKd"));
+        assert_eq!(Category::FullHouse, category("9c9d9hKsKd"));
+        assert_eq!(Category::Flush, category("2h7h9hJhKh"));
+        assert_eq!(Category::Straight, category("Ah2s3d4c5h"));
+        assert_eq!(Category::Straight, category("TsJdQhKcAh"));
+        assert_eq!(Category::HighCard, category("JsQdKhAc2h"));
+        assert_eq!(Category::ThreeOfAKind, category("7c7d7hKs2d"));
+        assert_eq!(Category::TwoPair, category("7c7dKhKs2d"));
+        assert_eq!(Category::OnePair, category("7c7dKhQs2d"));
+        assert_eq!(Category::HighCard, category("7c8dKhQs2d"));
+    }
+
+    #[test]
+    fn test_ordering() {
+        let hand = |cards: &str| Hand::new(cards, 0, &Poker);
+        // Stronger hands sort first
+        let strongest_first = [
+            "As2s3s4s5s", // Five high straight flush beats four of a kind
+            "AcAdAhAsKd",
+            "AcAdAhAsQd", // Kicker
+            "2c2d2hAsAd",
+            "KhQhJh9h8h",
+            "KhQhJh9h7h", // Flush compares every card
+            "2c3d4h5s6d",
+            "Ah2s3d4c5h", // The wheel is the lowest straight
+            "QcQdQhAs3d",
+            "JcJdTh9s9d",
+            "JcJd8h8s9d",
+            "JcJd8h8s7d",
+            "5c5dAhKsQd",
+            "AcKdQhJs9d",
+        ];
+        for (a, b) in strongest_first.iter().zip(&strongest_first[1..]) {
+            assert!(hand(a) < hand(b), "{a} should beat {b}");
+        }
+        // Suits don't break ties
+        assert_eq!(hand("AcKdQhJs9d"), hand("AhKsQdJc9s"));
+    }
+
+    #[test]
+    fn test_winnings() {
+        // Two pair (rank 2), the ace-high flush (rank 3), one pair (rank 1)
+        assert_eq!(
+            10 * 2 + 100 * 3 + 1,
+            winnings("JcJd8h8s7d 10\n2h7h9hJhAh 100\n5c5dAhKsQd 1", &Poker)
+        );
+    }
+}
@@ -0,0 +1,442 @@
+// Odds of each hand type when dealing from a deck, and of one hand beating another.
+//
+// A deck is every card it holds, with repeats. The exact odds go through every combination of
+// card counts a hand could have, weighted by the number of ways to pick those cards, so their
+// cost depends on the number of different cards rather than the size of the deck. Head to head,
+// the order of the cards matters for ties, so every ordered deal of the missing cards is tried.
+// When that's too many, `--samples` deals random hands instead.
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::{Game, HandType, Ruleset};
+
+/// Refuse to enumerate more deals than this
+const MAX_DEALS: u128 = 50_000_000;
+
+/// Marks a card that hasn't been dealt yet in a partial hand
+const UNKNOWN: char = '?';
+
+pub struct Deck {
+    cards: Vec<char>,
+}
+
+impl Deck {
+    /// `copies` of every card in `ruleset`
+    pub fn new(ruleset: &Ruleset, copies: usize) -> Deck {
+        Deck {
+            cards: ruleset
+                .order
+                .iter()
+                .flat_map(|c| std::iter::repeat_n(*c, copies))
+                .collect(),
+        }
+    }
+
+    /// Every card in the deck listed out, e.g. `AAKKQQJJJJ`
+    pub fn from_cards(cards: &str, ruleset: &Ruleset) -> Result<Deck, String> {
+        let cards = cards.chars().collect::<Vec<char>>();
+        match cards.iter().find(|c| !ruleset.order.contains(c)) {
+            Some(c) => Err(format!("{c} isn't a card in {} camel poker", ruleset.name)),
+            None => Ok(Deck { cards }),
+        }
+    }
+
+    /// The deck left once `dealt` is out of it
+    fn without(&self, dealt: &[char]) -> Result<Deck, String> {
+        let mut cards = self.cards.clone();
+        for c in dealt.iter().filter(|c| **c != UNKNOWN) {
+            let i = cards
+                .iter()
+                .position(|card| card == c)
+                .ok_or_else(|| format!("Not enough {c}s in the deck"))?;
+            cards.swap_remove(i);
+        }
+        Ok(Deck { cards })
+    }
+
+    /// Each different card with how many of it there are
+    fn counts(&self) -> (Vec<char>, Vec<u128>) {
+        let mut kinds = Vec::<char>::new();
+        let mut counts = Vec::<u128>::new();
+        for c in &self.cards {
+            match kinds.iter().position(|k| k == c) {
+                Some(i) => counts[i] += 1,
+                None => {
+                    kinds.push(*c);
+                    counts.push(1);
+                }
+            }
+        }
+        (kinds, counts)
+    }
+}
+
+fn choose(n: u128, k: u128) -> u128 {
+    (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+}
+
+/// How many hands of each type there are (strongest first) out of `total`
+#[derive(Debug, PartialEq)]
+pub struct Odds {
+    pub counts: Vec<(HandType, u128)>,
+    pub total: u128,
+}
+
+impl Odds {
+    fn from_counts(counts: HashMap<HandType, u128>, total: u128) -> Odds {
+        let mut counts = counts.into_iter().collect::<Vec<(HandType, u128)>>();
+        counts.sort();
+        Odds { counts, total }
+    }
+
+    pub fn probability(&self, htype: HandType) -> f64 {
+        self.counts
+            .iter()
+            .find(|(t, _)| *t == htype)
+            .map_or(0.0, |(_, n)| *n as f64 / self.total as f64)
+    }
+}
+
+/// Every way to take `left` more cards from those in `counts`, with the number of ways to pick them
+fn compositions(
+    counts: &[u128],
+    kinds: &[char],
+    left: usize,
+    hand: &mut Vec<char>,
+    ways: u128,
+    visit: &mut impl FnMut(&[char], u128),
+) {
+    if left == 0 {
+        visit(hand, ways);
+        return;
+    }
+    let Some((count, rest)) = counts.split_first() else {
+        return;
+    };
+    for taken in 0..=left.min(*count as usize) {
+        hand.extend(std::iter::repeat_n(kinds[0], taken));
+        compositions(
+            rest,
+            &kinds[1..],
+            left - taken,
+            hand,
+            ways * choose(*count, taken as u128),
+            visit,
+        );
+        hand.truncate(hand.len() - taken);
+    }
+}
+
+pub fn exact_odds(deck: &Deck, ruleset: &Ruleset) -> Result<Odds, String> {
+    let (kinds, counts) = deck.counts();
+    let size = ruleset.hand_size;
+    if deck.cards.len() < size {
+        return Err(format!(
+            "A deck of {} can't deal a hand of {size}",
+            deck.cards.len()
+        ));
+    }
+    if choose((kinds.len() + size - 1) as u128, size as u128) > MAX_DEALS {
+        return Err("Too many different hands to count, try --samples".to_string());
+    }
+
+    let mut by_type = HashMap::<HandType, u128>::new();
+    compositions(
+        &counts,
+        &kinds,
+        size,
+        &mut Vec::new(),
+        1,
+        &mut |hand, ways| {
+            *by_type.entry(ruleset.hand_type(hand)).or_insert(0) += ways;
+        },
+    );
+    Ok(Odds::from_counts(
+        by_type,
+        choose(deck.cards.len() as u128, size as u128),
+    ))
+}
+
+/// xorshift64*, so runs can be repeated from the same seed
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) % n as u64) as usize
+    }
+
+    /// Shuffle just the first `n` cards into place
+    fn deal(&mut self, cards: &mut [char], n: usize) {
+        for i in 0..n {
+            let j = i + self.below(cards.len() - i);
+            cards.swap(i, j);
+        }
+    }
+}
+
+pub fn sampled_odds(
+    deck: &Deck,
+    ruleset: &Ruleset,
+    samples: u64,
+    rng: &mut Rng,
+) -> Result<Odds, String> {
+    if samples == 0 {
+        return Err("Need at least one sample".to_string());
+    }
+    let size = ruleset.hand_size;
+    if deck.cards.len() < size {
+        return Err(format!(
+            "A deck of {} can't deal a hand of {size}",
+            deck.cards.len()
+        ));
+    }
+    let mut cards = deck.cards.clone();
+    let mut by_type = HashMap::<HandType, u128>::new();
+    for _ in 0..samples {
+        rng.deal(&mut cards, size);
+        *by_type
+            .entry(ruleset.hand_type(&cards[..size]))
+            .or_insert(0) += 1;
+    }
+    Ok(Odds::from_counts(by_type, samples as u128))
+}
+
+/// Chances of the first hand beating, tying with or losing to the second
+#[derive(Debug, PartialEq)]
+pub struct Matchup {
+    pub win: f64,
+    pub tie: f64,
+    pub lose: f64,
+}
+
+/// A partial hand, with `?` for the cards still to come. Short hands are filled up with `?`s.
+fn partial(cards: &str, ruleset: &Ruleset) -> Result<Vec<char>, String> {
+    let mut hand = cards.chars().collect::<Vec<char>>();
+    if hand.len() > ruleset.hand_size {
+        return Err(format!("{cards} has more than {} cards", ruleset.hand_size));
+    }
+    if let Some(c) = hand
+        .iter()
+        .find(|c| **c != UNKNOWN && !ruleset.order.contains(c))
+    {
+        return Err(format!("{c} isn't a card in {} camel poker", ruleset.name));
+    }
+    hand.resize(ruleset.hand_size, UNKNOWN);
+    Ok(hand)
+}
+
+/// The unknown cards of `a` then `b`, filled from `dealt` in order
+fn fill(a: &mut [char], b: &mut [char], dealt: &[char]) {
+    let unknown = a.iter_mut().chain(b.iter_mut()).filter(|c| **c == UNKNOWN);
+    for (slot, c) in unknown.zip(dealt) {
+        *slot = *c;
+    }
+}
+
+fn compare(ruleset: &Ruleset, a: &[char], b: &[char]) -> Ordering {
+    ruleset
+        .hand_type(a)
+        .cmp(&ruleset.hand_type(b))
+        .then_with(|| ruleset.tiebreak(a, b))
+}
+
+/// Every ordered deal of `slots` cards, with the number of ways to deal it
+fn deals(
+    counts: &mut [u128],
+    kinds: &[char],
+    slots: usize,
+    dealt: &mut Vec<char>,
+    ways: u128,
+    visit: &mut impl FnMut(&[char], u128),
+) {
+    if slots == 0 {
+        visit(dealt, ways);
+        return;
+    }
+    for i in 0..kinds.len() {
+        if counts[i] == 0 {
+            continue;
+        }
+        let count = counts[i];
+        counts[i] -= 1;
+        dealt.push(kinds[i]);
+        deals(counts, kinds, slots - 1, dealt, ways * count, visit);
+        dealt.pop();
+        counts[i] += 1;
+    }
+}
+
+pub fn exact_matchup(deck: &Deck, ruleset: &Ruleset, a: &str, b: &str) -> Result<Matchup, String> {
+    let (a, b) = (partial(a, ruleset)?, partial(b, ruleset)?);
+    let deck = deck.without(&[a.clone(), b.clone()].concat())?;
+    let slots = a.iter().chain(&b).filter(|c| **c == UNKNOWN).count();
+    if deck.cards.len() < slots {
+        return Err("Not enough cards left to finish both hands".to_string());
+    }
+    let (kinds, mut counts) = deck.counts();
+    if (kinds.len() as u128)
+        .checked_pow(slots as u32)
+        .is_none_or(|n| n > MAX_DEALS)
+    {
+        return Err("Too many deals to try, use --samples".to_string());
+    }
+
+    let (mut hand_a, mut hand_b) = (a.clone(), b.clone());
+    let mut outcomes = [0_u128; 3];
+    deals(
+        &mut counts,
+        &kinds,
+        slots,
+        &mut Vec::new(),
+        1,
+        &mut |dealt, ways| {
+            hand_a.copy_from_slice(&a);
+            hand_b.copy_from_slice(&b);
+            fill(&mut hand_a, &mut hand_b, dealt);
+            match compare(ruleset, &hand_a, &hand_b) {
+                // Stronger hands sort first
+                Ordering::Less => outcomes[0] += ways,
+                Ordering::Equal => outcomes[1] += ways,
+                Ordering::Greater => outcomes[2] += ways,
+            }
+        },
+    );
+    let total = outcomes.iter().sum::<u128>() as f64;
+    Ok(Matchup {
+        win: outcomes[0] as f64 / total,
+        tie: outcomes[1] as f64 / total,
+        lose: outcomes[2] as f64 / total,
+    })
+}
+
+pub fn sampled_matchup(
+    deck: &Deck,
+    ruleset: &Ruleset,
+    a: &str,
+    b: &str,
+    samples: u64,
+    rng: &mut Rng,
+) -> Result<Matchup, String> {
+    if samples == 0 {
+        return Err("Need at least one sample".to_string());
+    }
+    let (a, b) = (partial(a, ruleset)?, partial(b, ruleset)?);
+    let mut deck = deck.without(&[a.clone(), b.clone()].concat())?;
+    let slots = a.iter().chain(&b).filter(|c| **c == UNKNOWN).count();
+    if deck.cards.len() < slots {
+        return Err("Not enough cards left to finish both hands".to_string());
+    }
+
+    let (mut hand_a, mut hand_b) = (a.clone(), b.clone());
+    let mut outcomes = [0_u64; 3];
+    for _ in 0..samples {
+        rng.deal(&mut deck.cards, slots);
+        hand_a.copy_from_slice(&a);
+        hand_b.copy_from_slice(&b);
+        fill(&mut hand_a, &mut hand_b, &deck.cards[..slots]);
+        match compare(ruleset, &hand_a, &hand_b) {
+            Ordering::Less => outcomes[0] += 1,
+            Ordering::Equal => outcomes[1] += 1,
+            Ordering::Greater => outcomes[2] += 1,
+        }
+    }
+    Ok(Matchup {
+        win: outcomes[0] as f64 / samples as f64,
+        tie: outcomes[1] as f64 / samples as f64,
+        lose: outcomes[2] as f64 / samples as f64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::probability::{exact_matchup, exact_odds, sampled_matchup, sampled_odds, Deck, Rng};
+    use crate::{HandType, Ruleset};
+
+    #[test]
+    fn test_exact_odds() {
+        // Like poker with no suits, so flushes and straights are just high cards
+        let standard = Ruleset::standard();
+        let odds = exact_odds(&Deck::new(&standard, 4), &standard).unwrap();
+        assert_eq!(2_598_960, odds.total);
+        assert_eq!(
+            vec![
+                (HandType::FourOfAKind, 624),
+                (HandType::FullHouse, 3_744),
+                (HandType::ThreeOfAKind, 54_912),
+                (HandType::TwoPair, 123_552),
+                (HandType::OnePair, 1_098_240),
+                (HandType::HighCard, 1_317_888),
+            ],
+            odds.counts
+        );
+
+        // Jokers can only help
+        let jokers = Ruleset::jokers();
+        let joker_odds = exact_odds(&Deck::new(&jokers, 4), &jokers).unwrap();
+        assert_eq!(odds.total, joker_odds.total);
+        assert!(joker_odds.probability(HandType::FiveOfAKind) > 0.0);
+        assert!(joker_odds.probability(HandType::HighCard) < odds.probability(HandType::HighCard));
+    }
+
+    #[test]
+    fn test_sampled_odds() {
+        let jokers = Ruleset::jokers();
+        let deck = Deck::new(&jokers, 4);
+        let exact = exact_odds(&deck, &jokers).unwrap();
+        let sampled = sampled_odds(&deck, &jokers, 200_000, &mut Rng::new(7)).unwrap();
+        assert_eq!(200_000, sampled.total);
+        for (htype, _) in &exact.counts {
+            assert!((exact.probability(*htype) - sampled.probability(*htype)).abs() < 0.005);
+        }
+
+        assert_eq!(
+            Some("Need at least one sample".to_string()),
+            sampled_odds(&deck, &jokers, 0, &mut Rng::new(7)).err()
+        );
+    }
+
+    #[test]
+    fn test_matchup() {
+        // A deck of AAKKK: with A? against K?, the first hand wins with AA, or with AK against KA
+        let tiny = Ruleset::from_str("tiny: AK size 2");
+        let deck = Deck::from_cards("AAKKK", &tiny).unwrap();
+        let matchup = exact_matchup(&deck, &tiny, "A", "K").unwrap();
+        assert!((matchup.win - 2.0 / 3.0).abs() < 1e-12);
+        assert_eq!(0.0, matchup.tie);
+        assert!((matchup.lose - 1.0 / 3.0).abs() < 1e-12);
+
+        // Hands that start the same are evenly matched
+        let jokers = Ruleset::jokers();
+        let deck = Deck::new(&jokers, 4);
+        let even = exact_matchup(&deck, &jokers, "AKQ2", "AKQ2").unwrap();
+        assert!((even.win - even.lose).abs() < 1e-12);
+
+        let exact = exact_matchup(&deck, &jokers, "KKJ", "QQ2").unwrap();
+        let sampled =
+            sampled_matchup(&deck, &jokers, "KKJ", "QQ2", 100_000, &mut Rng::new(3)).unwrap();
+        assert!(exact.win > 0.8);
+        for (e, s) in [
+            (exact.win, sampled.win),
+            (exact.tie, sampled.tie),
+            (exact.lose, sampled.lose),
+        ] {
+            assert!((e - s).abs() < 0.01);
+        }
+        assert_eq!(
+            Some("Need at least one sample".to_string()),
+            sampled_matchup(&deck, &jokers, "KKJ", "QQ2", 0, &mut Rng::new(3)).err()
+        );
+
+        assert_eq!(
+            Some("Not enough As in the deck".to_string()),
+            exact_matchup(&Deck::from_cards("AAKK", &tiny).unwrap(), &tiny, "AA", "A?").err()
+        );
+    }
+}
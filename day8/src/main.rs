@@ -1,72 +1,693 @@
-use std::collections::HashMap;
-use num::integer::lcm;
+use num::integer::{ExtendedGcd, Integer};
+use std::{collections::HashMap, env, fs};
 
-fn main() {
-    // Part 1
-    fn parse_graph(input: &str) -> HashMap<&str, (&str, &str)> {
-        let mut graph = HashMap::<&str, (&str, &str)>::new();
+/// Refuse to try more combinations of the ghosts' end nodes than this
+const MAX_COMBINATIONS: u64 = 50_000_000;
+
+fn parse_identifier(s: &str) -> Option<&str> {
+    let s = s.trim();
+    let valid = !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '_');
+    valid.then_some(s)
+}
 
-        // We can guarentee structure is AAA = (BBB, CCC) so we can index in directly
-        for line in input.lines() {
-            graph.insert(&line[0..3], (&line[7..10], &line[12..15]));
+/// Each node's left and right neighbours
+type Graph<'a> = HashMap<&'a str, (&'a str, &'a str)>;
+
+/// Lines like `AAA = (BBB, CCC)`, with names of any length and any spacing. Errors count lines
+/// from `first_line`.
+fn parse_graph(input: &str, first_line: usize) -> Result<Graph<'_>, String> {
+    let mut graph = Graph::new();
+
+    for (i, line) in input
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+    {
+        let bad_line = || {
+            format!(
+                "Line {}: expected NODE = (LEFT, RIGHT), found '{line}'",
+                first_line + i
+            )
+        };
+        let (node, edges) = line.split_once('=').ok_or_else(bad_line)?;
+        let (left, right) = edges
+            .trim()
+            .strip_prefix('(')
+            .and_then(|edges| edges.strip_suffix(')'))
+            .and_then(|edges| edges.split_once(','))
+            .ok_or_else(bad_line)?;
+        let node = parse_identifier(node).ok_or_else(bad_line)?;
+        let edges = (
+            parse_identifier(left).ok_or_else(bad_line)?,
+            parse_identifier(right).ok_or_else(bad_line)?,
+        );
+        if graph.insert(node, edges).is_some() {
+            return Err(format!(
+                "Line {}: {node} is already in the network",
+                first_line + i
+            ));
         }
+    }
+
+    Ok(graph)
+}
+
+/// The instructions on the first line, then the network
+fn parse_input(input: &str) -> Result<(&str, Graph<'_>), String> {
+    let trimmed = input.trim_start();
+    let skipped = input[..input.len() - trimmed.len()].matches('\n').count();
+    let (lrorder, graph) = trimmed.split_once('\n').unwrap_or((trimmed, ""));
+    // The network starts on the line after the instructions
+    Ok((lrorder.trim(), parse_graph(graph, skipped + 2)?))
+}
+
+/// The network with node names swapped for indexes, so each step is a couple of array lookups
+struct Network<'a> {
+    names: Vec<&'a str>,
+    ids: HashMap<&'a str, usize>,
+    // Where left and right go from each node
+    edges: Vec<[usize; 2]>,
+    // The instructions, 0 for left and 1 for right
+    turns: Vec<usize>,
+}
 
-        graph
+impl<'a> Network<'a> {
+    fn new(lrorder: &str, graph: &Graph<'a>) -> Network<'a> {
+        let mut names = graph.keys().copied().collect::<Vec<&str>>();
+        names.sort();
+        let ids = names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (*name, id))
+            .collect::<HashMap<&str, usize>>();
+        let id = |name: &str| {
+            *ids.get(name)
+                .unwrap_or_else(|| panic!("{name} isn't in the network"))
+        };
+        let edges = names
+            .iter()
+            .map(|name| {
+                let (left, right) = graph[name];
+                [id(left), id(right)]
+            })
+            .collect();
+        let turns = lrorder
+            .trim()
+            .chars()
+            .map(|dir| match dir {
+                'L' => 0,
+                'R' => 1,
+                _ => panic!("Expected L or R, found {dir}"),
+            })
+            .collect::<Vec<usize>>();
+        if turns.is_empty() {
+            panic!("No instructions to follow");
+        }
+        Network {
+            names,
+            ids,
+            edges,
+            turns,
+        }
+    }
+
+    fn id(&self, name: &str) -> usize {
+        *self
+            .ids
+            .get(name)
+            .unwrap_or_else(|| panic!("{name} isn't in the network"))
+    }
+
+    /// Where the ghost on `node` goes next, when it's already taken `steps` steps
+    fn step(&self, node: usize, steps: u64) -> usize {
+        self.edges[node][self.turns[(steps % self.turns.len() as u64) as usize]]
     }
 
-    fn walk_graph(
-        lr_index: usize,
-        node: &str,
-        target: &str,
-        steps: u64,
-        lrorder: &Vec<char>,
-        graph: &HashMap<&str, (&str, &str)>,
-    ) -> u64 {
-        if node.ends_with(target) {
-            return steps;
+    /// The network in Graphviz's DOT language. Start (..A) nodes are green and end (..Z) nodes
+    /// red. A path (as visited nodes) is drawn over the top in blue.
+    fn to_dot(&self, path: &[usize]) -> String {
+        let quote = |node: usize| format!("\"{}\"", self.names[node]);
+        let mut path_edges = path
+            .windows(2)
+            .zip(0_u64..)
+            .map(|(w, steps)| (w[0], self.turns[(steps % self.turns.len() as u64) as usize]))
+            .collect::<Vec<(usize, usize)>>();
+        path_edges.sort();
+        path_edges.dedup();
+
+        let mut dot = "digraph network {\n".to_string();
+        for node in 0..self.names.len() {
+            let mut attributes = Vec::<&str>::new();
+            match self.names[node] {
+                name if name.ends_with('A') => {
+                    attributes.extend(["style=filled", "fillcolor=palegreen"])
+                }
+                name if name.ends_with('Z') => {
+                    attributes.extend(["style=filled", "fillcolor=lightcoral"])
+                }
+                _ => (),
+            }
+            if path.contains(&node) {
+                attributes.extend(["color=blue", "penwidth=2"]);
+            }
+            match attributes.is_empty() {
+                true => dot += &format!("  {};\n", quote(node)),
+                false => dot += &format!("  {} [{}];\n", quote(node), attributes.join(", ")),
+            }
         }
-        let dir = &lrorder[lr_index];
-        let next_node = match dir {
-            'L' => graph.get(node).unwrap().0,
-            'R' => graph.get(node).unwrap().1,
-            _ => panic!("Expected L or R, found {dir}"),
-        };
-        let next_lr_index = match lr_index {
-            lr_index if lr_index + 1 < lrorder.len() => lr_index + 1,
-            _ => 0,
+        for (node, [left, right]) in self.edges.iter().enumerate() {
+            // One edge when both ways go to the same place
+            let edges = match left == right {
+                true => vec![(*left, "LR", vec![0, 1])],
+                false => vec![(*left, "L", vec![0]), (*right, "R", vec![1])],
+            };
+            for (to, label, turns) in edges {
+                let on_path = turns.iter().any(|t| path_edges.contains(&(node, *t)));
+                dot += &format!(
+                    "  {} -> {} [label=\"{label}\"{}];\n",
+                    quote(node),
+                    quote(to),
+                    if on_path {
+                        ", color=blue, penwidth=2"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+        dot + "}\n"
+    }
+
+    /// Steps from `start` to the first end node
+    fn walk(&self, start: usize, is_end: impl Fn(usize) -> bool) -> Result<u64, String> {
+        // Any longer and the ghost is going round in circles
+        let limit = (self.names.len() * self.turns.len()) as u64;
+        let mut node = start;
+        for steps in 0..=limit {
+            if is_end(node) {
+                return Ok(steps);
+            }
+            node = self.step(node, steps);
+        }
+        Err(format!("{} never gets there", self.names[start]))
+    }
+}
+
+/// Where each node leads after 2^k passes through every instruction, so the ghost can skip ahead
+/// whole passes at a time (binary lifting)
+struct JumpTable {
+    passes: Vec<Vec<usize>>,
+}
+
+impl JumpTable {
+    fn new(network: &Network) -> JumpTable {
+        let len = network.turns.len() as u64;
+        let one_pass = (0..network.names.len())
+            .map(|start| (0..len).fold(start, |node, steps| network.step(node, steps)))
+            .collect::<Vec<usize>>();
+        // Enough levels for any number of steps that fits in a u64
+        let levels = 64 - (u64::MAX / len).leading_zeros() as usize;
+        let mut passes = vec![one_pass];
+        while passes.len() < levels {
+            let last = passes.last().unwrap();
+            passes.push(last.iter().map(|node| last[*node]).collect());
+        }
+        JumpTable { passes }
+    }
+
+    /// Where the ghost from `start` is after `steps` steps
+    fn position(&self, network: &Network, start: usize, steps: u64) -> usize {
+        let len = network.turns.len() as u64;
+        let (whole, rest) = (steps / len, steps % len);
+        let node = self
+            .passes
+            .iter()
+            .enumerate()
+            .filter(|(k, _)| whole >> k & 1 == 1)
+            .fold(start, |node, (_, pass)| pass[node]);
+        // Whole passes end back at the first instruction
+        (0..rest).fold(node, |node, steps| network.step(node, steps))
+    }
+}
+
+/// Where a ghost ends up going forever. A ghost's state is its node and how far through the
+/// instructions it is, so after at most (nodes × instructions) steps it's somewhere it has been
+/// before, and loops from there.
+#[derive(Debug, Clone, PartialEq)]
+struct Cycle {
+    // Steps before the ghost first enters the loop
+    tail: u64,
+    // Steps around the loop
+    period: u64,
+    // Steps before the loop where the ghost is on an end node
+    tail_hits: Vec<u64>,
+    // Steps in the first time around the loop where the ghost is on an end node. It's on an end
+    // node again every `period` steps after each of these.
+    cycle_hits: Vec<u64>,
+}
+
+impl Cycle {
+    fn find(network: &Network, start: usize, is_end: impl Fn(usize) -> bool) -> Cycle {
+        let len = network.turns.len();
+        // When the ghost was first at each node with each instruction next
+        let mut seen = vec![None; network.names.len() * len];
+        let mut hits = Vec::<u64>::new();
+        let mut node = start;
+        let mut steps = 0_u64;
+        loop {
+            let state = node * len + (steps % len as u64) as usize;
+            if let Some(tail) = seen[state] {
+                let (tail_hits, cycle_hits) = hits.into_iter().partition(|h| *h < tail);
+                return Cycle {
+                    tail,
+                    period: steps - tail,
+                    tail_hits,
+                    cycle_hits,
+                };
+            }
+            seen[state] = Some(steps);
+            if is_end(node) {
+                hits.push(steps);
+            }
+            node = network.step(node, steps);
+            steps += 1;
+        }
+    }
+
+    fn hits(&self, steps: u64) -> bool {
+        match steps < self.tail {
+            true => self.tail_hits.contains(&steps),
+            false => self
+                .cycle_hits
+                .iter()
+                .any(|h| (steps - self.tail) % self.period == (h - self.tail)),
+        }
+    }
+}
+
+/// x ≡ a₁ (mod m₁) and x ≡ a₂ (mod m₂) as a single x ≡ a (mod lcm(m₁, m₂)), if they agree.
+/// The moduli needn't be coprime. Errors rather than overflowing `i128`.
+fn crt((a1, m1): (i128, i128), (a2, m2): (i128, i128)) -> Result<Option<(i128, i128)>, String> {
+    let overflow = || format!("Combining {a1} (mod {m1}) with {a2} (mod {m2}) overflows");
+    // p·m₁ + q·m₂ = gcd(m₁, m₂)
+    let ExtendedGcd { gcd: g, x: p, .. } = m1.extended_gcd(&m2);
+    if (a2 - a1) % g != 0 {
+        return Ok(None);
+    }
+    let lcm = (m1 / g).checked_mul(m2).ok_or_else(overflow)?;
+    // Step from a₁ in multiples of m₁ until also ≡ a₂ (mod m₂)
+    let k = ((a2 - a1) / g % (m2 / g))
+        .checked_mul(p)
+        .ok_or_else(overflow)?
+        % (m2 / g);
+    let a = k
+        .checked_mul(m1)
+        .and_then(|km| a1.checked_add(km))
+        .ok_or_else(overflow)?;
+    Ok(Some((a.rem_euclid(lcm), lcm)))
+}
+
+/// First step where every ghost is on an end node at the same time
+fn first_common_hit(cycles: &[Cycle]) -> Result<u64, String> {
+    let Some(longest_tail) = cycles.iter().map(|c| c.tail).max() else {
+        return Err("No ghosts to follow".to_string());
+    };
+
+    // Before every ghost is in its loop, only the ghost with the longest tail has a short
+    // enough list of hits to just check them all
+    let longest = cycles.iter().find(|c| c.tail == longest_tail).unwrap();
+    let early = longest
+        .tail_hits
+        .iter()
+        .find(|h| cycles.iter().all(|c| c.hits(**h)));
+    if let Some(steps) = early {
+        return Ok(*steps);
+    }
+
+    // After that each ghost is on an end node at steps ≡ some hit (mod its period), so try
+    // every combination of those
+    if cycles
+        .iter()
+        .try_fold(1_u64, |n, c| n.checked_mul(c.cycle_hits.len() as u64))
+        .is_none_or(|n| n > MAX_COMBINATIONS)
+    {
+        return Err("Too many combinations of end nodes to try".to_string());
+    }
+    let mut best: Option<i128> = None;
+    let mut combinations = vec![(0_i128, 1_i128)];
+    for cycle in cycles {
+        let mut next = Vec::new();
+        for c in &combinations {
+            for h in &cycle.cycle_hits {
+                next.extend(crt(*c, (*h as i128, cycle.period as i128))?);
+            }
+        }
+        // Different hits can agree on the same steps
+        next.sort();
+        next.dedup();
+        combinations = next;
+    }
+    for (a, m) in combinations {
+        // The first such step once every ghost is in its loop
+        let floor = longest_tail as i128;
+        let steps = match a < floor {
+            true => ((floor - a - 1) / m + 1)
+                .checked_mul(m)
+                .and_then(|skip| a.checked_add(skip))
+                .ok_or("Overflow finding the first common step")?,
+            false => a,
         };
-        return walk_graph(next_lr_index, next_node, target, steps + 1, lrorder, graph);
+        if best.is_none_or(|b| steps < b) {
+            best = Some(steps);
+        }
     }
 
-    let (lrorder_str, graph_str) = include_str!("day8.txt").split_once("\n\n").unwrap();
+    let best = best.map(|steps| {
+        u64::try_from(steps).map_err(|_| format!("The first common step, {steps}, is too big"))
+    });
+    best.transpose()?.ok_or_else(|| {
+        format!(
+            "The ghosts are never all on an end node at once (periods {})",
+            cycles
+                .iter()
+                .map(|c| c.period.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    })
+}
 
-    let graph = parse_graph(graph_str);
-    let lrorder = lrorder_str.chars().collect::<Vec<char>>();
+fn main() {
+    // Part 1
+    let (lrorder_str, graph) =
+        parse_input(include_str!("day8.txt")).unwrap_or_else(|e| panic!("{e}"));
+    let network = Network::new(lrorder_str, &graph);
 
-    let steps_part_1 = walk_graph(0, "AAA", "ZZZ", 0, &lrorder, &graph);
+    let zzz = network.id("ZZZ");
+    let steps_part_1 = network
+        .walk(network.id("AAA"), |node| node == zzz)
+        .unwrap_or_else(|e| panic!("{e}"));
     println!("{:?}", steps_part_1);
 
     // Part 2
-    let start_nodes = graph
-        .keys()
-        .into_iter()
-        .map(|c| c.clone())
-        .filter(|node| node.chars().nth(2).unwrap() == 'A')
-        .collect::<Vec<&str>>();
-
-    // We can't brute force, so we must find the cycle length of each start node, and take the LCM!
-    // Re-use existing walk graph function, but exit on first node that ends with Z
-    let steps_lcm_part_2: u64 = start_nodes.iter().map(|start_node| {
-        walk_graph(
-            0,
-            &start_node,
-            "Z",
-            0,
-            &lrorder,
-            &graph,
+    let is_end = |node: usize| network.names[node].ends_with('Z');
+    let start_nodes = (0..network.names.len())
+        .filter(|node| network.names[*node].ends_with('A'))
+        .collect::<Vec<usize>>();
+
+    // We can't brute force, so find where each ghost loops, and when it's on a Z node within
+    // the loop, then find the first step where those all line up
+    let cycles = start_nodes
+        .iter()
+        .map(|start| Cycle::find(&network, *start, is_end))
+        .collect::<Vec<Cycle>>();
+    let steps_part_2 = first_common_hit(&cycles).unwrap_or_else(|e| panic!("{e}"));
+
+    println!("{:?}", steps_part_2);
+
+    // `--cycles` describes each ghost's loop, and `--after <node> <steps>` says where the ghost
+    // from that node is after that many steps. `--dot <path>` writes the network for Graphviz,
+    // with the ghost from `--dot-path <node>` followed until it starts going round in circles.
+    let mut jump_table = None;
+    let mut dot_path = None;
+    let mut dot_start = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cycles" => {
+                for (start, cycle) in start_nodes.iter().zip(&cycles) {
+                    println!(
+                        "{}: tail {}, period {}, Z at {:?} then {:?} + {}k",
+                        network.names[*start],
+                        cycle.tail,
+                        cycle.period,
+                        cycle.tail_hits,
+                        cycle.cycle_hits,
+                        cycle.period
+                    );
+                }
+            }
+            "--after" => {
+                let start = args.next().expect("--after needs a node");
+                let steps = args
+                    .next()
+                    .expect("--after needs a number of steps")
+                    .parse::<u64>()
+                    .expect("--after needs a number of steps");
+                let table = jump_table.get_or_insert_with(|| JumpTable::new(&network));
+                let node = table.position(&network, network.id(&start), steps);
+                println!("{start} after {steps} steps: {}", network.names[node]);
+            }
+            "--dot" => dot_path = Some(args.next().expect("--dot needs a path")),
+            "--dot-path" => dot_start = Some(args.next().expect("--dot-path needs a node")),
+            _ => panic!("Unknown argument {arg}"),
+        }
+    }
+
+    if let Some(path) = dot_path {
+        let ghost_path = match dot_start {
+            Some(start) => {
+                let start = network.id(&start);
+                let cycle = Cycle::find(&network, start, is_end);
+                (0..=cycle.tail + cycle.period)
+                    .scan(start, |node, steps| {
+                        let here = *node;
+                        *node = network.step(here, steps);
+                        Some(here)
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        fs::write(&path, network.to_dot(&ghost_path))
+            .unwrap_or_else(|e| panic!("Couldn't write {path}: {e}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{crt, first_common_hit, parse_graph, parse_input, Cycle, JumpTable, Network};
+
+    const EXAMPLE: &str = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+
+    // Always left, so each ghost's state is just its node
+    const GHOSTS: &str = "L
+
+11A = (11B, XXX)
+11B = (11Z, XXX)
+11Z = (11B, XXX)
+22A = (22Z, XXX)
+22Z = (22B, XXX)
+22B = (22Z, XXX)
+55A = (55Z, XXX)
+55Z = (55B, XXX)
+55B = (55C, XXX)
+55C = (55Z, XXX)
+77A = (77Z, XXX)
+77Z = (77B, XXX)
+77B = (78Z, XXX)
+78Z = (77C, XXX)
+77C = (77Z, XXX)
+99A = (99Z, XXX)
+99Z = (99B, XXX)
+99B = (99C, XXX)
+99C = (99B, XXX)
+XXX = (XXX, XXX)";
+
+    fn cycles(input: &str, starts: &[&str]) -> Vec<Cycle> {
+        let (lrorder, graph) = parse_input(input).unwrap();
+        let network = Network::new(lrorder, &graph);
+        starts
+            .iter()
+            .map(|start| {
+                Cycle::find(&network, network.id(start), |node| {
+                    network.names[node].ends_with('Z')
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(Ok(Some((4, 6))), crt((0, 2), (1, 3)));
+        // Not coprime, but they agree
+        assert_eq!(Ok(Some((10, 12))), crt((2, 4), (4, 6)));
+        assert_eq!(Ok(None), crt((0, 2), (1, 4)));
+        // The combined period doesn't fit
+        assert!(crt((0, (1 << 100) + 1), (1, (1 << 100) + 3)).is_err());
+
+        // Agrees at 466148062458368908367327463 steps, far past u64
+        let primes = [1_000_000_007, 1_000_000_009, 998_244_353];
+        let cycles = primes
+            .into_iter()
+            .zip([5, 7, 11])
+            .map(|(period, hit)| Cycle {
+                tail: 0,
+                period,
+                tail_hits: vec![],
+                cycle_hits: vec![hit],
+            })
+            .collect::<Vec<Cycle>>();
+        assert_eq!(
+            Err("The first common step, 466148062458368908367327463, is too big".to_string()),
+            first_common_hit(&cycles)
+        );
+
+        // Six ghosts with 50 end nodes each in their loops
+        let busy = Cycle {
+            tail: 0,
+            period: 100,
+            tail_hits: vec![],
+            cycle_hits: (0..100).step_by(2).collect(),
+        };
+        assert_eq!(
+            Err("Too many combinations of end nodes to try".to_string()),
+            first_common_hit(&vec![busy; 6])
+        );
+    }
+
+    #[test]
+    fn test_cycles() {
+        let found = cycles(GHOSTS, &["55A", "77A", "99A"]);
+        assert_eq!(
+            Cycle {
+                tail: 1,
+                period: 3,
+                tail_hits: vec![],
+                cycle_hits: vec![1]
+            },
+            found[0]
+        );
+        // Two Zs in one loop
+        assert_eq!(
+            (1, 4, vec![1, 3]),
+            (found[1].tail, found[1].period, found[1].cycle_hits.clone())
+        );
+        // Only passes a Z on the way in
+        assert_eq!(
+            (vec![1], vec![]),
+            (found[2].tail_hits.clone(), found[2].cycle_hits.clone())
+        );
+
+        assert_eq!(Ok(6), first_common_hit(&cycles(EXAMPLE, &["11A", "22A"])));
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        let starts = ["11A", "22A", "55A", "77A", "99A"];
+        let found = cycles(GHOSTS, &starts);
+        // Every pair and triple of ghosts
+        for a in 0..starts.len() {
+            for b in a + 1..starts.len() {
+                for c in b..starts.len() {
+                    let group = [a, b, c].map(|i| found[i].clone());
+                    let brute = (0..1000).find(|s| group.iter().all(|g| g.hits(*s)));
+                    assert_eq!(brute, first_common_hit(&group).ok(), "{a} {b} {c}");
+                }
+            }
+        }
+
+        // Even steps and odd steps never meet
+        assert!(first_common_hit(&cycles(GHOSTS, &["11A", "22A"])).is_err());
+        // The first Z for 11A is at 2 and 55A at 1, but they don't line up until 4
+        assert_eq!(Ok(4), first_common_hit(&cycles(GHOSTS, &["11A", "55A"])));
+    }
+
+    #[test]
+    fn test_jump_table() {
+        let (lrorder, graph) = parse_input(EXAMPLE).unwrap();
+        let network = Network::new(lrorder, &graph);
+        let table = JumpTable::new(&network);
+
+        let start = network.id("22A");
+        let mut node = start;
+        for steps in 0..100 {
+            assert_eq!(node, table.position(&network, start, steps));
+            node = network.step(node, steps);
+        }
+
+        // Far past the tail, the ghost's somewhere it has already been a whole number of loops ago
+        let cycle = &cycles(EXAMPLE, &["22A"])[0];
+        for steps in [10_u64.pow(15), u64::MAX - 1, u64::MAX] {
+            let earlier = cycle.tail + (steps - cycle.tail) % cycle.period;
+            assert_eq!(
+                table.position(&network, start, earlier),
+                table.position(&network, start, steps)
+            );
+        }
+
+        assert_eq!(
+            Ok(2),
+            network.walk(network.id("11A"), |node| node == network.id("11Z"))
+        );
+        assert!(network
+            .walk(network.id("11A"), |node| node == network.id("22Z"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let graph = parse_graph(
+            "start=(left_1 , start)\n\n  left_1   =   ( end,end )  \nend = (end, end)",
+            1,
         )
-    })
-    .fold(1, |acc, x| lcm(acc, x));
+        .unwrap();
+        assert_eq!(("left_1", "start"), graph["start"]);
+        assert_eq!(("end", "end"), graph["left_1"]);
 
-    println!("{:?}", steps_lcm_part_2);
+        assert_eq!(
+            Some("Line 2: expected NODE = (LEFT, RIGHT), found 'BBB = CCC, DDD'".to_string()),
+            parse_graph("AAA = (BBB, CCC)\nBBB = CCC, DDD", 1).err()
+        );
+        assert!(parse_graph("AAA = (BBB, C C)", 1).is_err());
+        assert_eq!(
+            Some("Line 2: AAA is already in the network".to_string()),
+            parse_graph("AAA = (BBB, CCC)\nAAA = (CCC, CCC)", 1).err()
+        );
+
+        let (lrorder, graph) = parse_input("\n  LRL \n\nAAA = (AAA, AAA)\n").unwrap();
+        assert_eq!("LRL", lrorder);
+        assert_eq!(1, graph.len());
+
+        // Lines count from the top of the file, instructions and blank lines included
+        assert_eq!(
+            Some("Line 5: expected NODE = (LEFT, RIGHT), found 'BBB'".to_string()),
+            parse_input("\n  LRL \n\nAAA = (AAA, AAA)\nBBB").err()
+        );
+        assert_eq!(
+            Some("Line 4: AAA is already in the network".to_string()),
+            parse_input("LR\n\nAAA = (AAA, AAA)\nAAA = (AAA, AAA)").err()
+        );
+    }
+
+    #[test]
+    fn test_dot() {
+        let (lrorder, graph) =
+            parse_input("LR\n\nAA = (B, ZZ)\nB = (B, ZZ)\nZZ = (ZZ, ZZ)").unwrap();
+        let network = Network::new(lrorder, &graph);
+        let (aa, b, zz) = (network.id("AA"), network.id("B"), network.id("ZZ"));
+        assert_eq!(
+            "digraph network {
+  \"AA\" [style=filled, fillcolor=palegreen, color=blue, penwidth=2];
+  \"B\" [color=blue, penwidth=2];
+  \"ZZ\" [style=filled, fillcolor=lightcoral, color=blue, penwidth=2];
+  \"AA\" -> \"B\" [label=\"L\", color=blue, penwidth=2];
+  \"AA\" -> \"ZZ\" [label=\"R\"];
+  \"B\" -> \"B\" [label=\"L\"];
+  \"B\" -> \"ZZ\" [label=\"R\", color=blue, penwidth=2];
+  \"ZZ\" -> \"ZZ\" [label=\"LR\"];
+}
+",
+            network.to_dot(&[aa, b, zz])
+        );
+    }
 }
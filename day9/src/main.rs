@@ -1,41 +1,266 @@
-fn main() {
-    fn parse_history(line: &str) -> Vec<i64> {
-        line.split_ascii_whitespace()
-            .map(|value| value.parse().unwrap())
-            .collect::<Vec<i64>>()
+use std::{env, fmt};
+
+use num::{BigInt, BigRational, One, Signed, Zero};
+
+fn parse_history(line: &str) -> Vec<BigInt> {
+    line.split_ascii_whitespace()
+        .map(|value| value.parse().unwrap())
+        .collect::<Vec<BigInt>>()
+}
+
+/// An index like `7`, `-2`, `1/3` or `2.5`
+fn parse_index(index: &str) -> Result<BigRational, String> {
+    let bad_index = || format!("{index} isn't an integer, fraction or decimal");
+    match index.split_once('.') {
+        Some((whole, fraction)) => {
+            let digits = format!("{whole}{fraction}")
+                .parse::<BigInt>()
+                .map_err(|_| bad_index())?;
+            let scale = num::pow(BigInt::from(10), fraction.len());
+            Ok(BigRational::new(digits, scale))
+        }
+        None => index.parse::<BigRational>().map_err(|_| bad_index()),
     }
+}
+
+/// The lowest degree polynomial through every value of a history, with the first value at
+/// index 0. Coefficients are exact fractions, lowest power first.
+#[derive(Debug, Clone, PartialEq)]
+struct Polynomial {
+    coefficients: Vec<BigRational>,
+}
+
+impl Polynomial {
+    /// Newton's forward difference formula: p(x) = Σ Δᵏy₀ · C(x, k), where Δᵏy₀ is the first
+    /// value on the kth row of differences and C(x, k) = x(x - 1)…(x - k + 1) / k!. Rows stop
+    /// once they're all zero, which gives the degree.
+    fn fit(history: &[BigInt]) -> Polynomial {
+        let mut coefficients = Vec::<BigRational>::new();
+        // C(x, k) expanded into powers of x
+        let mut binomial = vec![BigRational::one()];
+        let mut diffs = history.to_vec();
+
+        for k in 0_u64.. {
+            if diffs.iter().all(Zero::is_zero) {
+                break;
+            }
+
+            let leading = BigRational::from_integer(diffs[0].clone());
+            coefficients.resize(binomial.len(), BigRational::zero());
+            for (coefficient, b) in coefficients.iter_mut().zip(&binomial) {
+                *coefficient += &leading * b;
+            }
 
-    fn predict(history: Vec<i64>) -> i64 {
-        // Bottom level reached
-        if history.iter().all(|value| value == &0) {
-            return 0;
-        };
+            // C(x, k + 1) = C(x, k) · (x - k) / (k + 1)
+            let k = BigRational::from_integer(k.into());
+            let mut next = vec![BigRational::zero(); binomial.len() + 1];
+            for (power, b) in binomial.iter().enumerate() {
+                next[power + 1] += b;
+                next[power] -= b * &k;
+            }
+            let k_plus_one = k + BigRational::one();
+            binomial = next.into_iter().map(|b| b / &k_plus_one).collect();
+
+            diffs = diffs.windows(2).map(|w| &w[1] - &w[0]).collect();
+        }
+
+        // Leading terms can cancel out
+        while coefficients.last().is_some_and(Zero::is_zero) {
+            coefficients.pop();
+        }
+        Polynomial { coefficients }
+    }
 
-        let diffs = history[1..]
+    /// None for the zero polynomial
+    fn degree(&self) -> Option<usize> {
+        self.coefficients.len().checked_sub(1)
+    }
+
+    /// The value at any index, whole or not
+    fn at(&self, x: &BigRational) -> BigRational {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(BigRational::zero(), |value, coefficient| {
+                value * x + coefficient
+            })
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut terms = self
+            .coefficients
             .iter()
             .enumerate()
-            .map(|(i, value)| value - history[i])
-            .collect::<Vec<i64>>();
+            .rev()
+            .filter(|(_, coefficient)| !coefficient.is_zero())
+            .peekable();
+
+        if terms.peek().is_none() {
+            return write!(f, "0");
+        }
 
-        return predict(diffs) + history.last().unwrap();
+        let mut first = true;
+        for (power, coefficient) in terms {
+            match (first, coefficient.is_negative()) {
+                (true, true) => write!(f, "-")?,
+                (false, true) => write!(f, " - ")?,
+                (false, false) => write!(f, " + ")?,
+                (true, false) => (),
+            }
+            first = false;
+
+            let magnitude = coefficient.abs();
+            match (power, magnitude.is_one()) {
+                (0, _) => write!(f, "{magnitude}")?,
+                (_, true) => (),
+                (_, false) => write!(f, "{magnitude}·")?,
+            }
+            match power {
+                0 => (),
+                1 => write!(f, "x")?,
+                _ => write!(f, "x^{power}")?,
+            }
+        }
+        Ok(())
     }
+}
 
-    // Part 1
-    let prediction_sum: i64 = include_str!("day9.txt")
+/// Sum over every history of its value at `index`
+fn sum_at(polynomials: &[Polynomial], index: &BigRational) -> BigRational {
+    polynomials
+        .iter()
+        .map(|polynomial| polynomial.at(index))
+        .sum()
+}
+
+fn main() {
+    let histories = include_str!("day9.txt")
         .lines()
         .map(parse_history)
-        .map(predict)
+        .collect::<Vec<Vec<BigInt>>>();
+    let polynomials = histories
+        .iter()
+        .map(|history| Polynomial::fit(history))
+        .collect::<Vec<Polynomial>>();
+
+    // Part 1
+    let prediction_sum: BigRational = polynomials
+        .iter()
+        .zip(&histories)
+        .map(|(polynomial, history)| {
+            polynomial.at(&BigRational::from_integer(history.len().into()))
+        })
         .sum();
 
     println!("{}", prediction_sum);
 
     // Part 2
-    let prediction_sum_rev: i64 = include_str!("day9.txt")
-        .lines()
-        .map(parse_history)
-        .map(|history| history.into_iter().rev().collect())
-        .map(predict)
-        .sum();
+    let prediction_sum_rev = sum_at(&polynomials, &-BigRational::one());
 
     println!("{}", prediction_sum_rev);
+
+    // `--polynomials` prints the polynomial behind each history, and `--at <index>` sums
+    // every history at that index, which can be negative or fractional
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--polynomials" => {
+                for polynomial in &polynomials {
+                    match polynomial.degree() {
+                        Some(degree) => println!("{polynomial} (degree {degree})"),
+                        None => println!("{polynomial}"),
+                    }
+                }
+            }
+            "--at" => {
+                let index = args.next().expect("--at needs an index");
+                let index = parse_index(&index).unwrap_or_else(|e| panic!("{e}"));
+                println!("{}", sum_at(&polynomials, &index));
+            }
+            _ => panic!("Unknown argument {arg}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::{BigInt, BigRational};
+
+    use crate::{parse_history, parse_index, sum_at, Polynomial};
+
+    const EXAMPLE: &str = "0 3 6 9 12 15\n1 3 6 10 15 21\n10 13 16 21 30 45";
+
+    fn fit(history: &str) -> Polynomial {
+        Polynomial::fit(&parse_history(history))
+    }
+
+    fn ratio(numer: i64, denom: i64) -> BigRational {
+        BigRational::new(numer.into(), denom.into())
+    }
+
+    #[test]
+    fn test_example() {
+        let polynomials = EXAMPLE.lines().map(fit).collect::<Vec<Polynomial>>();
+        assert_eq!(ratio(114, 1), sum_at(&polynomials, &ratio(6, 1)));
+        assert_eq!(ratio(2, 1), sum_at(&polynomials, &ratio(-1, 1)));
+    }
+
+    #[test]
+    fn test_fit() {
+        // Triangle numbers: (x² + 3x + 2) / 2
+        let triangles = fit("1 3 6 10 15 21");
+        assert_eq!(
+            vec![ratio(1, 1), ratio(3, 2), ratio(1, 2)],
+            triangles.coefficients
+        );
+        assert_eq!(Some(2), triangles.degree());
+        assert_eq!("1/2·x^2 + 3/2·x + 1", triangles.to_string());
+
+        assert_eq!("-x^3 + 5", fit("5 4 -3 -22").to_string());
+        assert_eq!("7", fit("7 7 7").to_string());
+        assert_eq!("0", fit("0 0 0").to_string());
+        assert_eq!(None, fit("0 0 0").degree());
+        assert_eq!(None, fit("").degree());
+    }
+
+    #[test]
+    fn test_any_index() {
+        let triangles = fit("1 3 6 10 15 21");
+        assert_eq!(ratio(1, 1), triangles.at(&ratio(0, 1)));
+        assert_eq!(ratio(28, 1), triangles.at(&ratio(6, 1)));
+        assert_eq!(ratio(0, 1), triangles.at(&ratio(-1, 1)));
+        assert_eq!(ratio(1, 1), triangles.at(&ratio(-3, 1)));
+        // Halfway between 1 and 3, less a bit for the curve
+        assert_eq!(ratio(15, 8), triangles.at(&ratio(1, 2)));
+        assert_eq!(ratio(-1, 8), triangles.at(&ratio(-3, 2)));
+    }
+
+    #[test]
+    fn test_big_values() {
+        // Going up 2⁶² a step passes i64::MAX by index 2
+        let line = fit("0 4611686018427387904");
+        let expected = "13835058055282163712".parse::<BigInt>().unwrap();
+        assert_eq!(BigRational::from_integer(expected), line.at(&ratio(3, 1)));
+
+        // And values that start beyond i64 entirely
+        let squares = fit("100000000000000000000 100000000000000000001 100000000000000000004");
+        let expected = "100000000000000000009".parse::<BigInt>().unwrap();
+        assert_eq!(
+            BigRational::from_integer(expected),
+            squares.at(&ratio(3, 1))
+        );
+    }
+
+    #[test]
+    fn test_parse_index() {
+        assert_eq!(Ok(ratio(7, 1)), parse_index("7"));
+        assert_eq!(Ok(ratio(-2, 1)), parse_index("-2"));
+        assert_eq!(Ok(ratio(1, 3)), parse_index("1/3"));
+        assert_eq!(Ok(ratio(5, 2)), parse_index("2.5"));
+        assert_eq!(Ok(ratio(-1, 20)), parse_index("-0.05"));
+        assert!(parse_index("x").is_err());
+        assert!(parse_index("1.2.3").is_err());
+    }
 }